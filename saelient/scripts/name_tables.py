@@ -0,0 +1,81 @@
+INDUSTRY_GROUPS = {
+    "0": "IndustryGroup::Global",
+    "1": "IndustryGroup::OnHighway",
+    "2": "IndustryGroup::AgriculturalAndForestry",
+    "3": "IndustryGroup::Construction",
+    "4": "IndustryGroup::Marine",
+    "5": "IndustryGroup::IndustrialProcess",
+}
+
+# max_width in rustfmt.toml
+MAX_WIDTH = 80
+
+
+def pattern(column, industry_group):
+    # `*` applies to every industry group, vehicle system or value
+    column = column.strip()
+    if column == "*":
+        return "_"
+    if industry_group:
+        return INDUSTRY_GROUPS[column]
+    return column
+
+
+def match_case(key, name):
+    # Lay the arm out the way rustfmt does, so the output can be pasted
+    # unchanged: arms that don't fit in MAX_WIDTH move the name into a block
+    arm = f"        {key} => \"{name}\",\n"
+    block = f"            \"{name}\"\n"
+    if len(arm) - 1 <= MAX_WIDTH or len(block) - 1 > MAX_WIDTH:
+        return arm
+    return f"        {key} => {{\n{block}        }}\n"
+
+
+def generate_rust_match(input_filename, output_filename, key_columns):
+    try:
+        with open(input_filename, 'r') as file:
+            lines = file.readlines()
+
+        match_cases = []
+
+        for line in lines:
+            if line.startswith("#"):
+                continue
+
+            # Split the line into the key columns and the name
+            parts = line.rstrip("\n").split("\t")
+            # Check if the line is valid (contains all keys and a name)
+            if len(parts) >= key_columns + 1:
+                # Industry group is the first column, everything else is a number
+                keys = [pattern(part, index == 0) for index, part in enumerate(parts[:key_columns])]
+                name = parts[key_columns].strip().replace('\"', '\\\"')  # Escape double quotes
+                key = f"({', '.join(keys)})"
+                match_cases.append(match_case(key, name))
+
+        # Open the output Rust file
+        with open(output_filename, 'w') as output_file:
+            # Write the start of the match block
+            output_file.write("    Some(match key {\n")
+
+            # Write all the match cases
+            output_file.writelines(match_cases)
+
+            # Write the fallback case and close the match block
+            output_file.write(
+                "        _ => return None,\n"
+                "    })\n"
+            )
+        print(f"Match statement successfully generated in {output_filename}")
+
+    except FileNotFoundError:
+        print(f"Error: The file '{input_filename}' was not found.")
+    except Exception as e:
+        print(f"An error occurred: {e}")
+
+
+# Columns: industry group, vehicle system, function, name
+generate_rust_match('functions.txt', 'function_match.rs', 3)
+# Columns: industry group, vehicle system, name
+generate_rust_match('vehicle_systems.txt', 'vehicle_system_match.rs', 2)
+# Columns: industry group, address, name
+generate_rust_match('preferred_addresses.txt', 'preferred_address_match.rs', 2)
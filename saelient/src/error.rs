@@ -0,0 +1,255 @@
+//! Error types.
+//!
+//! Each module returns the error type covering what can go wrong there, all
+//! of which convert into the crate wide [`Error`]. None of them allocate and
+//! with the `std` feature enabled they implement [`std::error::Error`].
+
+/// Reasons a [`J1939Id`](crate::identifier::J1939Id) could not be built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum IdError {
+    /// Priority is larger than 7.
+    InvalidPriority,
+    /// PDU2 PGN sent to something other than the global address.
+    DestinationNotGlobal,
+    /// Both extended data page and data page are set, which is reserved by
+    /// ISO 15765-3.
+    ReservedDataPage,
+    /// Identifier is not an extended 29-bit identifier.
+    NotExtended,
+}
+
+/// Reasons a frame can't be viewed as a J1939 frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FrameError {
+    /// Frame has a standard 11-bit identifier.
+    StandardId,
+    /// Frame is a remote frame.
+    Remote,
+}
+
+/// Message encoding and decoding errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MessageError {
+    /// Payload does not have the expected length.
+    InvalidLength,
+    /// Control byte is reserved or unknown.
+    InvalidControlByte,
+    /// Parameter group number is larger than 18 bits.
+    InvalidPgn,
+    /// Identifier does not carry the expected parameter group.
+    UnexpectedPgn,
+    /// Broadcast message sent to something other than the global address.
+    DestinationNotGlobal,
+    /// Frame is not a J1939 data frame with an extended identifier.
+    InvalidFrame,
+    /// Output buffer is too small for the encoded message.
+    BufferTooSmall,
+}
+
+/// NAME field that is out of range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NameError {
+    /// Identity number does not fit in 21 bits.
+    Identity,
+    /// Manufacturer code does not fit in 11 bits.
+    ManufacturerCode,
+    /// ECU instance does not fit in 3 bits.
+    EcuInstance,
+    /// Function instance does not fit in 5 bits.
+    FunctionInstance,
+    /// Vehicle system does not fit in 7 bits.
+    VehicleSystem,
+    /// Vehicle system instance does not fit in 4 bits.
+    VehicleSystemInstance,
+    /// Industry group is reserved.
+    IndustryGroup,
+}
+
+/// Signal value errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SignalError {
+    /// Value is larger than the signal can hold.
+    OutOfRange,
+    /// Value is reserved or not defined for the signal.
+    InvalidValue,
+}
+
+/// Slot limit that was violated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SlotError {
+    /// Value is below the lower limit of the slot.
+    BelowMinimum,
+    /// Value is above the upper limit of the slot.
+    AboveMaximum,
+    /// Value is NaN.
+    NotANumber,
+}
+
+/// Transport protocol errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TransportError {
+    /// Connection management or data transfer message is malformed.
+    InvalidMessage,
+    /// Message is larger than the buffer it is reassembled into.
+    TooLarge,
+    /// Packet arrived with an unexpected sequence number.
+    BadSequence,
+    /// No packet arrived in time.
+    Timeout,
+    /// Another session is already in progress.
+    Busy,
+    /// Session was aborted by the other node or the application.
+    Aborted,
+}
+
+/// Network management errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NetworkError {
+    /// Message can't be sent to a single destination, either because of its
+    /// priority, its PDU2 parameter group or a payload over 8 bytes.
+    InvalidMessage,
+    /// No controller application with a matching NAME holds an address.
+    Unresolved,
+    /// No room left to queue the message.
+    QueueFull,
+}
+
+/// Any error returned by this crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// Identifier error.
+    Id(IdError),
+    /// Frame error.
+    Frame(FrameError),
+    /// Message error.
+    Message(MessageError),
+    /// NAME error.
+    Name(NameError),
+    /// Signal error.
+    Signal(SignalError),
+    /// Slot error.
+    Slot(SlotError),
+    /// Transport protocol error.
+    Transport(TransportError),
+    /// Network management error.
+    Network(NetworkError),
+}
+
+/// Implements `Display`, `std::error::Error` and conversion into [`Error`].
+macro_rules! error {
+    ($name:ident, $variant:ident, { $($value:ident => $description:literal,)* }) => {
+        impl core::fmt::Display for $name {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                f.write_str(match self {
+                    $(Self::$value => $description,)*
+                })
+            }
+        }
+
+        #[cfg(feature = "std")]
+        impl std::error::Error for $name {}
+
+        impl From<$name> for Error {
+            fn from(value: $name) -> Self {
+                Error::$variant(value)
+            }
+        }
+    };
+}
+
+error!(IdError, Id, {
+    InvalidPriority => "priority larger than 7",
+    DestinationNotGlobal => "PDU2 PGN with non-global destination",
+    ReservedDataPage => "extended data page and data page both set",
+    NotExtended => "not an extended identifier",
+});
+
+error!(FrameError, Frame, {
+    StandardId => "standard identifier",
+    Remote => "remote frame",
+});
+
+error!(MessageError, Message, {
+    InvalidLength => "invalid payload length",
+    InvalidControlByte => "invalid control byte",
+    InvalidPgn => "invalid PGN",
+    UnexpectedPgn => "unexpected PGN",
+    DestinationNotGlobal => "broadcast message with non-global destination",
+    InvalidFrame => "invalid frame",
+    BufferTooSmall => "buffer too small",
+});
+
+error!(NameError, Name, {
+    Identity => "identity number out of range",
+    ManufacturerCode => "manufacturer code out of range",
+    EcuInstance => "ECU instance out of range",
+    FunctionInstance => "function instance out of range",
+    VehicleSystem => "vehicle system out of range",
+    VehicleSystemInstance => "vehicle system instance out of range",
+    IndustryGroup => "reserved industry group",
+});
+
+error!(SignalError, Signal, {
+    OutOfRange => "signal value out of range",
+    InvalidValue => "invalid signal value",
+});
+
+error!(SlotError, Slot, {
+    BelowMinimum => "slot value below minimum",
+    AboveMaximum => "slot value above maximum",
+    NotANumber => "slot value is NaN",
+});
+
+error!(TransportError, Transport, {
+    InvalidMessage => "invalid transport message",
+    TooLarge => "transport message too large",
+    BadSequence => "bad sequence number",
+    Timeout => "transport timeout",
+    Busy => "transport session busy",
+    Aborted => "transport session aborted",
+});
+
+error!(NetworkError, Network, {
+    InvalidMessage => "message can't be sent to a NAME",
+    Unresolved => "no address for NAME",
+    QueueFull => "send queue full",
+});
+
+impl From<FrameError> for MessageError {
+    fn from(_: FrameError) -> Self {
+        MessageError::InvalidFrame
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Id(error) => error.fmt(f),
+            Error::Frame(error) => error.fmt(f),
+            Error::Message(error) => error.fmt(f),
+            Error::Name(error) => error.fmt(f),
+            Error::Signal(error) => error.fmt(f),
+            Error::Slot(error) => error.fmt(f),
+            Error::Transport(error) => error.fmt(f),
+            Error::Network(error) => error.fmt(f),
+        }
+    }
+}
+
+impl From<MessageError> for TransportError {
+    fn from(_: MessageError) -> Self {
+        TransportError::InvalidMessage
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
@@ -0,0 +1,451 @@
+//! Hardware acceptance filters.
+//!
+//! Most CAN controllers can drop frames in hardware using a small number of
+//! identifier and mask pairs. [`Filters`] packs the parameter groups and
+//! addresses a node is interested in into a fixed number of those pairs,
+//! widening the masks as needed and keeping track of how many unwanted
+//! identifiers get through as a result.
+//!
+//! Priority bits are never part of the mask, so frames are accepted regardless
+//! of their priority.
+
+use crate::identifier::{Address, Pgn};
+
+/// Bits of the identifier used for matching, everything except priority.
+const MATCH_BITS: u32 = 0x03FF_FFFF;
+
+/// Number of identifier bits that take part in matching.
+const MATCH_BIT_COUNT: u32 = MATCH_BITS.count_ones();
+
+/// Identifier and mask pair for a 29-bit extended identifier.
+///
+/// A frame is accepted when `frame_id & mask == id & mask`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Filter {
+    id: u32,
+    mask: u32,
+    /// Number of identifiers matched by this filter that were asked for, or
+    /// a lower bound once overlapping filters have been merged.
+    wanted: u64,
+}
+
+impl Filter {
+    const EMPTY: Filter = Filter {
+        id: 0,
+        mask: 0,
+        wanted: 0,
+    };
+
+    fn pattern(
+        pgn: Option<Pgn>,
+        destination: Option<Address>,
+        source: Option<Address>,
+    ) -> Self {
+        let mut id = 0;
+        let mut mask = 0;
+
+        if let Some(pgn) = pgn {
+            id |= pgn.as_raw() << 8;
+            mask |= if pgn.is_pdu1() {
+                0x3FF_0000
+            } else {
+                0x3FF_FF00
+            };
+
+            if let (true, Some(destination)) = (pgn.is_pdu1(), destination) {
+                id |= (destination.as_raw() as u32) << 8;
+                mask |= 0xFF00;
+            }
+        } else if let Some(destination) = destination {
+            id |= (destination.as_raw() as u32) << 8;
+            mask |= 0xFF00;
+        }
+
+        if let Some(source) = source {
+            id |= source.as_raw() as u32;
+            mask |= 0xFF;
+        }
+
+        let mut filter = Self {
+            id: id & mask,
+            mask,
+            wanted: 0,
+        };
+        filter.wanted = filter.size();
+        if pgn.is_none() && destination.is_some() {
+            // PDU2 parameter groups, PDU format 240 and up, whose group
+            // extension happens to equal the destination
+            filter.wanted -= filter.size() / 16;
+        }
+        filter
+    }
+
+    /// Identifier to match against.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Mask of the identifier bits that must match.
+    pub fn mask(&self) -> u32 {
+        self.mask
+    }
+
+    /// Returns `true` if the filter accepts the given raw identifier.
+    pub fn matches(&self, id: u32) -> bool {
+        id & self.mask == self.id
+    }
+
+    /// Number of identifiers, ignoring priority, accepted by this filter but
+    /// not asked for.
+    ///
+    /// Exact unless overlapping filters were merged into this one, in which
+    /// case it is an upper bound.
+    pub fn false_positives(&self) -> u64 {
+        self.size() - self.wanted
+    }
+
+    /// Register values for bxCAN filter banks in 32-bit mask mode.
+    ///
+    /// Returns the `FR1` and `FR2` values. Only extended data frames are
+    /// accepted.
+    pub fn bxcan(&self) -> (u32, u32) {
+        const IDE: u32 = 1 << 2;
+        const RTR: u32 = 1 << 1;
+
+        ((self.id << 3) | IDE, (self.mask << 3) | IDE | RTR)
+    }
+
+    /// FDCAN extended filter element using the classic filter type.
+    ///
+    /// Matching frames are stored in Rx FIFO 0.
+    pub fn fdcan(&self) -> [u32; 2] {
+        const EFEC_FIFO0: u32 = 0b001 << 29;
+        const EFT_CLASSIC: u32 = 0b10 << 30;
+
+        [EFEC_FIFO0 | self.id, EFT_CLASSIC | self.mask]
+    }
+
+    /// MCP2515 `RXFnSIDH`, `RXFnSIDL`, `RXFnEID8` and `RXFnEID0` values.
+    pub fn mcp2515_filter(&self) -> [u8; 4] {
+        const EXIDE: u8 = 1 << 3;
+
+        let mut registers = mcp2515_registers(self.id);
+        registers[1] |= EXIDE;
+        registers
+    }
+
+    /// MCP2515 `RXMnSIDH`, `RXMnSIDL`, `RXMnEID8` and `RXMnEID0` values.
+    ///
+    /// Masks are shared between filters on this controller, so each filter
+    /// needs its own receive buffer to be used unchanged.
+    pub fn mcp2515_mask(&self) -> [u8; 4] {
+        mcp2515_registers(self.mask)
+    }
+
+    fn size(&self) -> u64 {
+        1 << (MATCH_BIT_COUNT - (self.mask & MATCH_BITS).count_ones())
+    }
+
+    /// Number of identifiers accepted by both `self` and `other`.
+    fn overlap(&self, other: &Filter) -> u64 {
+        if (self.id ^ other.id) & self.mask & other.mask != 0 {
+            return 0;
+        }
+
+        Filter {
+            id: 0,
+            mask: self.mask | other.mask,
+            wanted: 0,
+        }
+        .size()
+    }
+
+    fn covers(&self, other: &Filter) -> bool {
+        self.mask & other.mask == self.mask && other.id & self.mask == self.id
+    }
+
+    fn merge(&self, other: &Filter) -> Filter {
+        let mask = self.mask & other.mask & !(self.id ^ other.id);
+        let mut merged = Filter {
+            id: self.id & mask,
+            mask,
+            // identifiers wanted by both would be counted twice
+            wanted: (self.wanted + other.wanted)
+                .saturating_sub(self.overlap(other)),
+        };
+        merged.wanted = merged.wanted.min(merged.size());
+        merged
+    }
+
+    /// Number of extra identifiers accepted if `self` and `other` are merged.
+    fn merge_cost(&self, other: &Filter) -> u64 {
+        self.merge(other)
+            .false_positives()
+            .saturating_sub(self.false_positives() + other.false_positives())
+    }
+}
+
+fn mcp2515_registers(value: u32) -> [u8; 4] {
+    [
+        (value >> 21) as u8,
+        (((value >> 18) & 0b111) as u8) << 5 | ((value >> 16) & 0b11) as u8,
+        (value >> 8) as u8,
+        value as u8,
+    ]
+}
+
+/// Set of at most `N` acceptance filters.
+///
+/// `N` is the number of hardware filter banks available and must not be zero.
+#[derive(Debug, Clone)]
+pub struct Filters<const N: usize> {
+    filters: [Filter; N],
+    len: usize,
+}
+
+impl<const N: usize> Default for Filters<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Filters<N> {
+    const NOT_EMPTY: () = assert!(N > 0, "at least one filter bank required");
+
+    /// Create an empty set that accepts nothing.
+    pub fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::NOT_EMPTY;
+
+        Self {
+            filters: [Filter::EMPTY; N],
+            len: 0,
+        }
+    }
+
+    /// Generate filters for every combination of PGN, destination and source.
+    ///
+    /// An empty slice accepts any value for that field. Destination addresses
+    /// only apply to PDU1 parameter groups, include [`Address::GLOBAL`] to
+    /// also receive messages sent to all nodes. Without PGNs, PDU2 parameter
+    /// groups whose group extension equals a destination get through too and
+    /// are counted as false positives.
+    pub fn generate(
+        pgns: &[Pgn],
+        destinations: &[Address],
+        sources: &[Address],
+    ) -> Self {
+        let mut filters = Self::new();
+
+        for_each_or_any(pgns, |pgn| {
+            for_each_or_any(destinations, |destination| {
+                for_each_or_any(sources, |source| {
+                    filters.accept(pgn, destination, source);
+                })
+            })
+        });
+
+        filters
+    }
+
+    /// Add a pattern to accept, `None` accepts any value for that field.
+    ///
+    /// When all banks are in use the two filters that let through the fewest
+    /// extra identifiers once combined are merged.
+    pub fn accept(
+        &mut self,
+        pgn: Option<Pgn>,
+        destination: Option<Address>,
+        source: Option<Address>,
+    ) {
+        let pattern = Filter::pattern(pgn, destination, source);
+
+        if self.filters().iter().any(|filter| filter.covers(&pattern)) {
+            return;
+        }
+
+        if self.len < N {
+            self.filters[self.len] = pattern;
+            self.len += 1;
+            return;
+        }
+
+        // cheapest merge is either between two existing filters or between
+        // the new pattern and one of the existing filters
+        let mut best = (u64::MAX, 0, None);
+
+        for i in 0..self.len {
+            let cost = self.filters[i].merge_cost(&pattern);
+            if cost < best.0 {
+                best = (cost, i, None);
+            }
+
+            for j in (i + 1)..self.len {
+                let cost = self.filters[i].merge_cost(&self.filters[j]);
+                if cost < best.0 {
+                    best = (cost, i, Some(j));
+                }
+            }
+        }
+
+        match best {
+            (_, i, None) => {
+                self.filters[i] = self.filters[i].merge(&pattern);
+            }
+            (_, i, Some(j)) => {
+                self.filters[i] = self.filters[i].merge(&self.filters[j]);
+                self.filters[j] = pattern;
+            }
+        }
+    }
+
+    /// Filters in use.
+    pub fn filters(&self) -> &[Filter] {
+        &self.filters[..self.len]
+    }
+
+    /// Returns `true` if any of the filters accepts the raw identifier.
+    pub fn matches(&self, id: u32) -> bool {
+        self.filters().iter().any(|filter| filter.matches(id))
+    }
+
+    /// Upper bound of the number of identifiers, ignoring priority, let
+    /// through but not asked for.
+    ///
+    /// Identifiers accepted by several overlapping filters are counted once
+    /// per filter.
+    pub fn false_positives(&self) -> u64 {
+        self.filters().iter().map(Filter::false_positives).sum()
+    }
+}
+
+fn for_each_or_any<T: Copy>(values: &[T], mut f: impl FnMut(Option<T>)) {
+    if values.is_empty() {
+        f(None);
+    } else {
+        values.iter().for_each(|value| f(Some(*value)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EEC1: Pgn = match Pgn::new(61444) {
+        Some(pgn) => pgn,
+        None => unreachable!(),
+    };
+    const DM1: Pgn = match Pgn::new(65226) {
+        Some(pgn) => pgn,
+        None => unreachable!(),
+    };
+    const REQUEST: Pgn = match Pgn::new(59904) {
+        Some(pgn) => pgn,
+        None => unreachable!(),
+    };
+
+    #[test]
+    fn exact() {
+        let filters = Filters::<4>::generate(&[EEC1, DM1], &[], &[]);
+
+        assert_eq!(filters.filters().len(), 2);
+        assert_eq!(filters.false_positives(), 0);
+        assert!(filters.matches(0x0CF0_0400));
+        assert!(filters.matches(0x18FE_CA17));
+        assert!(!filters.matches(0x18FE_CB17));
+    }
+
+    #[test]
+    fn destination_only_pdu1() {
+        let filters = Filters::<4>::generate(
+            &[REQUEST, DM1],
+            &[Address::new(0x21), Address::GLOBAL],
+            &[],
+        );
+
+        assert_eq!(filters.false_positives(), 0);
+        assert!(filters.matches(0x18EA_21F9));
+        assert!(filters.matches(0x18EA_FFF9));
+        assert!(!filters.matches(0x18EA_22F9));
+        assert!(filters.matches(0x18FE_CA00));
+    }
+
+    #[test]
+    fn destination_without_pgn() {
+        let filters = Filters::<4>::generate(&[], &[Address::new(0x21)], &[]);
+
+        assert_eq!(filters.filters()[0].mask(), 0xFF00);
+        assert!(filters.matches(0x18EA_21F9));
+        assert!(filters.matches(0x0CEF_2100));
+        assert!(!filters.matches(0x18EA_22F9));
+        assert!(!filters.matches(0x18FE_CA00));
+        // PDU2 groups with 0x21 as group extension
+        assert!(filters.matches(0x18FE_2100));
+        assert_eq!(filters.false_positives(), 1 << 14);
+
+        let mut filters = Filters::<4>::new();
+        filters.accept(None, Some(Address::new(0x21)), Some(0x00.into()));
+        assert_eq!(filters.filters()[0].mask(), 0xFFFF);
+        assert_eq!(filters.false_positives(), 1 << 6);
+    }
+
+    #[test]
+    fn merged() {
+        let filters = Filters::<1>::generate(
+            &[DM1],
+            &[],
+            &[Address::new(0x00), Address::new(0x01)],
+        );
+
+        assert_eq!(filters.filters().len(), 1);
+        assert_eq!(filters.false_positives(), 0);
+
+        let filters = Filters::<1>::generate(
+            &[DM1],
+            &[],
+            &[Address::new(0x00), Address::new(0x03)],
+        );
+
+        assert_eq!(filters.false_positives(), 2);
+        assert!(filters.matches(0x18FE_CA00));
+        assert!(filters.matches(0x18FE_CA03));
+        assert!(filters.matches(0x18FE_CA01));
+        assert!(!filters.matches(0x18FE_CA04));
+    }
+
+    #[test]
+    fn overlapping() {
+        let mut filters = Filters::<2>::new();
+        filters.accept(Some(DM1), None, None);
+        filters.accept(None, None, Some(Address::new(0x00)));
+
+        // DM1 from 0x00 is accepted by both filters
+        assert_eq!(filters.filters().len(), 2);
+        assert_eq!(filters.false_positives(), 0);
+
+        // the merged filter accepts everything, DM1 from 0x00 is only
+        // wanted once
+        let mut filters = Filters::<1>::new();
+        filters.accept(Some(DM1), None, None);
+        filters.accept(None, None, Some(Address::new(0x00)));
+
+        assert_eq!(filters.filters()[0].mask(), 0);
+        assert_eq!(
+            filters.false_positives(),
+            (1 << 26) - (1 << 8) - (1 << 18) + 1
+        );
+    }
+
+    #[test]
+    fn registers() {
+        let filters = Filters::<1>::generate(&[EEC1], &[], &[]);
+        let filter = filters.filters()[0];
+
+        assert_eq!(filter.id(), 0x00F0_0400);
+        assert_eq!(filter.mask(), 0x03FF_FF00);
+        assert_eq!(filter.bxcan(), (0x0780_2004, 0x1FFF_F806));
+        assert_eq!(filter.fdcan(), [0x20F0_0400, 0x83FF_FF00]);
+        assert_eq!(filter.mcp2515_filter(), [0x07, 0x88, 0x04, 0x00]);
+        assert_eq!(filter.mcp2515_mask(), [0x1F, 0xE3, 0xFF, 0x00]);
+    }
+}
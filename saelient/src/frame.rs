@@ -0,0 +1,102 @@
+//! Frame views.
+
+use crate::error::FrameError;
+use crate::identifier::{Address, Extended, Pgn, Priority, SourceAddress};
+use embedded_can::{ExtendedId, Frame, Id};
+
+/// J1939 view over a received frame.
+///
+/// Gives typed access to the fields of the identifier and the payload
+/// without copying the frame.
+#[derive(Debug, Clone, Copy)]
+pub struct J1939Frame<'a, F: Frame> {
+    frame: &'a F,
+    id: ExtendedId,
+}
+
+impl<'a, F: Frame> J1939Frame<'a, F> {
+    /// Create a view over `frame`.
+    pub fn new(frame: &'a F) -> Result<Self, FrameError> {
+        if frame.is_remote_frame() {
+            return Err(FrameError::Remote);
+        }
+
+        match frame.id() {
+            Id::Extended(id) => Ok(Self { frame, id }),
+            Id::Standard(_) => Err(FrameError::StandardId),
+        }
+    }
+
+    /// Extended identifier of the frame.
+    pub fn id(&self) -> ExtendedId {
+        self.id
+    }
+
+    /// Message priority.
+    pub fn priority(&self) -> u8 {
+        self.id.priority()
+    }
+
+    /// Parameter group number.
+    pub fn pgn(&self) -> Pgn {
+        self.id.pgn()
+    }
+
+    /// Message source address.
+    pub fn source_address(&self) -> Address {
+        self.id.source_address()
+    }
+
+    /// Destination address, global for PDU2 parameter groups.
+    pub fn destination_address(&self) -> Address {
+        self.id.destination_address().unwrap_or(Address::GLOBAL)
+    }
+
+    /// Frame payload.
+    pub fn data(&self) -> &'a [u8] {
+        self.frame.data()
+    }
+
+    /// Underlying frame.
+    pub fn frame(&self) -> &'a F {
+        self.frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestFrame;
+    use embedded_can::StandardId;
+
+    #[test]
+    fn view() {
+        let id = ExtendedId::new(0x18EA_00F9).unwrap();
+        let frame = TestFrame::new(id, &[0xEB, 0xFE, 0x00]).unwrap();
+        let view = J1939Frame::new(&frame).unwrap();
+
+        assert_eq!(view.id(), id);
+        assert_eq!(view.priority(), 6);
+        assert_eq!(view.pgn(), Pgn::new(59904).unwrap());
+        assert_eq!(view.source_address(), Address::new(0xF9));
+        assert_eq!(view.destination_address(), Address::new(0x00));
+        assert_eq!(view.data(), &[0xEB, 0xFE, 0x00]);
+
+        let id = ExtendedId::new(0x0CF0_0400).unwrap();
+        let frame = TestFrame::new(id, &[0; 8]).unwrap();
+        let view = J1939Frame::new(&frame).unwrap();
+
+        assert_eq!(view.pgn(), Pgn::new(61444).unwrap());
+        assert_eq!(view.destination_address(), Address::GLOBAL);
+    }
+
+    #[test]
+    fn rejected() {
+        let frame = TestFrame::new(StandardId::ZERO, &[]).unwrap();
+        assert_eq!(J1939Frame::new(&frame).err(), Some(FrameError::StandardId));
+
+        let id = ExtendedId::new(0x18EA_00F9).unwrap();
+        let frame = TestFrame::new_remote(id, 3).unwrap();
+        assert_eq!(J1939Frame::new(&frame).err(), Some(FrameError::Remote));
+    }
+}
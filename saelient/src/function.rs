@@ -0,0 +1,249 @@
+//! Function information.
+
+use crate::name::IndustryGroup;
+
+/// Returns the name of a NAME function.
+///
+/// Functions 0 to 127 mean the same in every industry group, the meaning of
+/// 128 to 255 depends on the industry group and vehicle system.
+///
+/// Returns `None` for functions missing from `scripts/functions.txt`, which
+/// lists the parts of the J1939DA table that are not extracted yet.
+pub const fn function_name(
+    function: u8,
+    industry_group: IndustryGroup,
+    vehicle_system: u8,
+) -> Option<&'static str> {
+    let key = (industry_group, vehicle_system, function);
+
+    // generated by scripts/name_tables.py from scripts/functions.txt
+    Some(match key {
+        (_, _, 0) => "Engine",
+        (_, _, 1) => "Auxiliary Power Unit (APU)",
+        (_, _, 2) => "Electric Propulsion Control",
+        (_, _, 3) => "Transmission",
+        (_, _, 4) => "Battery Pack Monitor",
+        (_, _, 5) => "Shift Control/Console",
+        (_, _, 6) => "Power TakeOff - (Main or Rear)",
+        (_, _, 7) => "Axle - Steering",
+        (_, _, 8) => "Axle - Drive",
+        (_, _, 9) => "Brakes - System Controller",
+        (_, _, 10) => "Brakes - Steer Axle",
+        (_, _, 11) => "Brakes - Drive axle",
+        (_, _, 12) => "Retarder - Engine",
+        (_, _, 13) => "Retarder - Driveline",
+        (_, _, 14) => "Cruise Control",
+        (_, _, 15) => "Fuel System",
+        (_, _, 16) => "Steering Controller",
+        (_, _, 17) => "Suspension - Steer Axle",
+        (_, _, 18) => "Suspension - Drive Axle",
+        (_, _, 19) => "Instrument Cluster",
+        (_, _, 20) => "Trip Recorder",
+        (_, _, 21) => "Cab Climate Control",
+        (_, _, 22) => "Aerodynamic Control",
+        (_, _, 23) => "Vehicle Navigation",
+        (_, _, 24) => "Vehicle Security",
+        (_, _, 25) => "Network Interconnect ECU",
+        (_, _, 26) => "Body Controller",
+        (_, _, 27) => "Power TakeOff (Secondary or Front)",
+        (_, _, 28) => "Off Vehicle Gateway",
+        (_, _, 29) => "Virtual Terminal (in cab)",
+        (_, _, 30) => "Management Computer",
+        (_, _, 31) => "Propulsion Battery Charger",
+        (_, _, 32) => "Headway Controller",
+        (_, _, 33) => "System Monitor",
+        (_, _, 34) => "Hydraulic Pump Controller",
+        (_, _, 35) => "Suspension - System Controller",
+        (_, _, 36) => "Pneumatic - System Controller",
+        (_, _, 37) => "Cab Controller",
+        (_, _, 38) => "Tire Pressure Control",
+        (_, _, 39) => "Ignition Control Module",
+        (_, _, 40) => "Seat Control",
+        (_, _, 41) => "Lighting - Operator Controls",
+        (_, _, 42) => "Water Pump Control",
+        (_, _, 43) => "Transmission Display",
+        (_, _, 44) => "Exhaust Emission Control",
+        (_, _, 45) => "Vehicle Dynamic Stability Control",
+        (_, _, 46) => "Oil Sensor Unit",
+        (_, _, 47) => "Information System Controller",
+        (_, _, 48) => "Ramp Control",
+        (_, _, 49) => "Clutch/Converter Control",
+        (_, _, 50) => "Auxiliary Heater",
+        (_, _, 51) => "Forward-Looking Collision Warning System",
+        (_, _, 52) => "Chassis Controller",
+        (_, _, 53) => "Alternator/Charging System",
+        (_, _, 54) => "Communications Unit, Cellular",
+        (_, _, 55) => "Communications Unit, Satellite",
+        (_, _, 56) => "Communications Unit, Radio",
+        (_, _, 57) => "Steering Column Unit",
+        (_, _, 58) => "Fan Drive Control",
+        (_, _, 59) => "Starter",
+        (_, _, 60) => "Cab Display",
+        (_, _, 61) => "File Server / Printer",
+        (_, _, 62) => "On-Board Diagnostic Unit",
+        (_, _, 63) => "Engine Valve Controller",
+        (_, _, 64) => "Endurance Braking",
+        (_, _, 65) => "Gas Flow Measurement",
+        (_, _, 66) => "I/O Controller",
+        (_, _, 67) => "Electrical System Controller",
+        (_, _, 68) => "Aftertreatment system gas measurement",
+        (_, _, 69) => "Engine Emission Aftertreatment System",
+        (_, _, 70) => "Auxiliary Regeneration Device",
+        (_, _, 71) => "Transfer Case Control",
+        (_, _, 72) => "Coolant Valve Controller",
+        (_, _, 73) => "Rollover Detection Control",
+        (_, _, 74) => "Lubrication System",
+        (_, _, 75) => "Supplemental Fan",
+        (_, _, 76) => "Temperature Sensor",
+        (_, _, 77) => "Fuel Properties Sensor",
+        (_, _, 78) => "Fire Suppression System",
+        (_, _, 79) => "Power Systems Manager",
+        (IndustryGroup::AgriculturalAndForestry, _, 128) => {
+            "Non-Virtual Terminal Display - Implement Bus"
+        }
+        (IndustryGroup::AgriculturalAndForestry, _, 129) => {
+            "Operator Controls - Machine Specific"
+        }
+        (IndustryGroup::AgriculturalAndForestry, _, 130) => {
+            "Task Controller (Mapping Computer)"
+        }
+        (IndustryGroup::AgriculturalAndForestry, _, 131) => "Position Control",
+        (IndustryGroup::AgriculturalAndForestry, _, 132) => "Machine Control",
+        (IndustryGroup::AgriculturalAndForestry, _, 133) => {
+            "Foreign Object Detection"
+        }
+        (IndustryGroup::AgriculturalAndForestry, _, 134) => "Tractor ECU",
+        (IndustryGroup::Marine, 10, 130) => "Diagnostic",
+        (IndustryGroup::Marine, 10, 140) => "Bus Traffic Logger",
+        (IndustryGroup::Marine, 20, 130) => "Man Overboard",
+        (IndustryGroup::Marine, 25, 130) => "PC Gateway",
+        (IndustryGroup::Marine, 25, 131) => "NMEA 2000 to Analog Gateway",
+        (IndustryGroup::Marine, 25, 132) => "Analog to NMEA 2000 Gateway",
+        (IndustryGroup::Marine, 25, 135) => "NMEA 0183 Gateway",
+        (IndustryGroup::Marine, 25, 136) => "NMEA Network Gateway",
+        (IndustryGroup::Marine, 25, 137) => "NMEA 2000 Wireless Gateway",
+        (IndustryGroup::Marine, 25, 140) => "Router",
+        (IndustryGroup::Marine, 25, 150) => "Bridge",
+        (IndustryGroup::Marine, 25, 160) => "Repeater",
+        (IndustryGroup::Marine, 30, 130) => "Binary Event Monitor",
+        (IndustryGroup::Marine, 30, 140) => "Load Controller",
+        (IndustryGroup::Marine, 30, 141) => "AC/DC Input",
+        (IndustryGroup::Marine, 30, 150) => "Function Controller",
+        (IndustryGroup::Marine, 35, 140) => "Engine",
+        (IndustryGroup::Marine, 35, 141) => "DC Generator/Alternator",
+        (IndustryGroup::Marine, 35, 142) => "Solar Panel",
+        (IndustryGroup::Marine, 35, 143) => "Wind Generator",
+        (IndustryGroup::Marine, 35, 144) => "Fuel Cell",
+        (IndustryGroup::Marine, 35, 145) => "Network Power Supply",
+        (IndustryGroup::Marine, 35, 151) => "AC Generator",
+        (IndustryGroup::Marine, 35, 152) => "AC Bus",
+        (IndustryGroup::Marine, 35, 153) => "AC Mains (Utility/Shore)",
+        (IndustryGroup::Marine, 35, 154) => "AC Output",
+        (IndustryGroup::Marine, 35, 160) => "Power Converter - Battery Charger",
+        (IndustryGroup::Marine, 35, 161) => {
+            "Power Converter - Battery Charger+Inverter"
+        }
+        (IndustryGroup::Marine, 35, 162) => "Power Converter - Inverter",
+        (IndustryGroup::Marine, 35, 163) => "Power Converter - DC",
+        (IndustryGroup::Marine, 35, 170) => "Battery",
+        (IndustryGroup::Marine, 35, 180) => "Engine Gateway",
+        (IndustryGroup::Marine, 40, 130) => "Follow-up Controller",
+        (IndustryGroup::Marine, 40, 140) => "Mode Controller",
+        (IndustryGroup::Marine, 40, 150) => "Autopilot",
+        (IndustryGroup::Marine, 40, 155) => "Rudder",
+        (IndustryGroup::Marine, 40, 160) => "Heading Sensors",
+        (IndustryGroup::Marine, 40, 170) => "Trim (Tabs)/Interceptors",
+        (IndustryGroup::Marine, 40, 180) => "Attitude (Pitch, Roll) Control",
+        (IndustryGroup::Marine, 50, 130) => "Engineroom Monitoring",
+        (IndustryGroup::Marine, 50, 140) => "Engine",
+        (IndustryGroup::Marine, 50, 141) => "DC Generator/Alternator",
+        (IndustryGroup::Marine, 50, 150) => "Engine Controller",
+        (IndustryGroup::Marine, 50, 151) => "AC Generator",
+        (IndustryGroup::Marine, 50, 155) => "Motor",
+        (IndustryGroup::Marine, 50, 160) => "Engine Gateway",
+        (IndustryGroup::Marine, 50, 165) => "Transmission",
+        (IndustryGroup::Marine, 50, 170) => "Throttle/Shift Control",
+        (IndustryGroup::Marine, 50, 180) => "Actuator",
+        (IndustryGroup::Marine, 50, 190) => "Gauge Interface",
+        (IndustryGroup::Marine, 50, 200) => "Gauge Large",
+        (IndustryGroup::Marine, 50, 210) => "Gauge Small",
+        (IndustryGroup::Marine, 60, 130) => "Bottom Depth",
+        (IndustryGroup::Marine, 60, 135) => "Bottom Depth/Speed",
+        (IndustryGroup::Marine, 60, 140) => "Ownship Attitude",
+        (IndustryGroup::Marine, 60, 145) => "Ownship Position (GNSS)",
+        (IndustryGroup::Marine, 60, 150) => "Ownship Position (Loran C)",
+        (IndustryGroup::Marine, 60, 155) => "Speed",
+        (IndustryGroup::Marine, 60, 160) => "Turn Rate Indicator",
+        (IndustryGroup::Marine, 60, 170) => "Integrated Navigation",
+        (IndustryGroup::Marine, 60, 175) => "Integrated Navigation System",
+        (IndustryGroup::Marine, 60, 190) => "Navigation Management",
+        (IndustryGroup::Marine, 60, 195) => {
+            "Automatic Identification System (AIS)"
+        }
+        (IndustryGroup::Marine, 60, 200) => "Radar",
+        (IndustryGroup::Marine, 60, 201) => "Infrared Imaging",
+        (IndustryGroup::Marine, 60, 205) => "ECDIS",
+        (IndustryGroup::Marine, 60, 210) => "ECS",
+        (IndustryGroup::Marine, 60, 220) => "Direction Finder",
+        (IndustryGroup::Marine, 60, 230) => "Voyage Status",
+        (IndustryGroup::Marine, 70, 130) => "EPIRB",
+        (IndustryGroup::Marine, 70, 140) => "AIS",
+        (IndustryGroup::Marine, 70, 150) => "DSC",
+        (IndustryGroup::Marine, 70, 160) => "Data Receiver/Transceiver",
+        (IndustryGroup::Marine, 70, 170) => "Satellite",
+        (IndustryGroup::Marine, 70, 180) => "Radio-telephone (MF/HF)",
+        (IndustryGroup::Marine, 70, 190) => "Radiotelephone",
+        (IndustryGroup::Marine, 75, 130) => "Temperature",
+        (IndustryGroup::Marine, 75, 140) => "Pressure",
+        (IndustryGroup::Marine, 75, 150) => "Fluid Level",
+        (IndustryGroup::Marine, 75, 160) => "Flow",
+        (IndustryGroup::Marine, 75, 170) => "Humidity",
+        (IndustryGroup::Marine, 80, 130) => "Time/Date Systems",
+        (IndustryGroup::Marine, 80, 140) => "VDR",
+        (IndustryGroup::Marine, 80, 150) => "Integrated Instrumentation",
+        (IndustryGroup::Marine, 80, 160) => "General Purpose Displays",
+        (IndustryGroup::Marine, 80, 170) => "General Sensor Box",
+        (IndustryGroup::Marine, 80, 180) => "Weather Instruments",
+        (IndustryGroup::Marine, 80, 190) => "Transducer/General",
+        (IndustryGroup::Marine, 80, 200) => "NMEA 0183 Converter",
+        (IndustryGroup::Marine, 85, 130) => "Atmospheric",
+        (IndustryGroup::Marine, 85, 160) => "Aquatic",
+        (IndustryGroup::Marine, 90, 130) => "HVAC",
+        (IndustryGroup::Marine, 120, 130) => "Display",
+        (IndustryGroup::Marine, 120, 140) => "Alarm Enunciator",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample() {
+        // global functions don't depend on the industry group
+        assert_eq!(function_name(0, IndustryGroup::Global, 0), Some("Engine"));
+        assert_eq!(function_name(0, IndustryGroup::Marine, 50), Some("Engine"));
+        assert_eq!(
+            function_name(29, IndustryGroup::AgriculturalAndForestry, 1),
+            Some("Virtual Terminal (in cab)")
+        );
+
+        assert_eq!(
+            function_name(145, IndustryGroup::Marine, 60),
+            Some("Ownship Position (GNSS)")
+        );
+        assert_eq!(function_name(145, IndustryGroup::Marine, 50), None);
+        assert_eq!(
+            function_name(134, IndustryGroup::AgriculturalAndForestry, 1),
+            Some("Tractor ECU")
+        );
+        assert_eq!(
+            function_name(130, IndustryGroup::AgriculturalAndForestry, 0),
+            Some("Task Controller (Mapping Computer)")
+        );
+        assert_eq!(function_name(134, IndustryGroup::Construction, 1), None);
+        assert_eq!(function_name(145, IndustryGroup::OnHighway, 60), None);
+        assert_eq!(function_name(127, IndustryGroup::Global, 0), None);
+    }
+}
@@ -1,14 +1,83 @@
 //! Message identifier.
 
+use crate::error::IdError;
+use core::ops::RangeInclusive;
 use embedded_can::{ExtendedId, Id, StandardId};
 
+/// Node address.
+///
+/// Addresses 0 to 253 identify a single node. 254 is the null address used by
+/// nodes that could not claim an address and 255 is the global address used
+/// to send to all nodes.
+///
+/// See SAEJ1939-81 4.1.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Address(u8);
+
+impl Address {
+    /// Global address, destination of messages sent to all nodes.
+    pub const GLOBAL: Address = Address(255);
+
+    /// Null address, source of messages from nodes without a claimed address.
+    pub const NULL: Address = Address(254);
+
+    /// Range of addresses that identify a single node.
+    pub const UNICAST: RangeInclusive<u8> = 0..=253;
+
+    /// Create a new `Address` from its raw value.
+    pub const fn new(raw: u8) -> Self {
+        Self(raw)
+    }
+
+    /// Create a new `Address` only if it identifies a single node.
+    pub const fn unicast(raw: u8) -> Option<Self> {
+        if raw <= *Self::UNICAST.end() {
+            Some(Self(raw))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the raw address value.
+    pub const fn as_raw(&self) -> u8 {
+        self.0
+    }
+
+    /// Address is the global address.
+    pub const fn is_global(&self) -> bool {
+        self.0 == Self::GLOBAL.0
+    }
+
+    /// Address is the null address.
+    pub const fn is_null(&self) -> bool {
+        self.0 == Self::NULL.0
+    }
+
+    /// Address identifies a single node.
+    pub const fn is_unicast(&self) -> bool {
+        self.0 <= *Self::UNICAST.end()
+    }
+}
+
+impl From<u8> for Address {
+    fn from(value: u8) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Address> for u8 {
+    fn from(value: Address) -> u8 {
+        value.0
+    }
+}
+
 pub trait SourceAddress {
     /// Message source address.
-    fn source_address(&self) -> u8;
+    fn source_address(&self) -> Address;
 }
 
 impl SourceAddress for Id {
-    fn source_address(&self) -> u8 {
+    fn source_address(&self) -> Address {
         match self {
             Id::Standard(id) => id.source_address(),
             Id::Extended(id) => id.source_address(),
@@ -34,8 +103,8 @@ impl Priority for Id {
 pub trait Standard {}
 
 impl SourceAddress for StandardId {
-    fn source_address(&self) -> u8 {
-        (self.as_raw() & 0xFF) as u8
+    fn source_address(&self) -> Address {
+        Address((self.as_raw() & 0xFF) as u8)
     }
 }
 
@@ -45,12 +114,119 @@ impl Priority for StandardId {
     }
 }
 
+/// Parameter Group Number.
+///
+/// An 18-bit value made up of the extended data page, data page, PDU format
+/// and PDU specific fields. For PDU1 (destination specific) groups the PDU
+/// specific field holds the destination address and is not part of the PGN,
+/// so it is always zero.
+///
+/// See SAEJ1939-21 5.1.2
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pgn(u32);
+
+impl Pgn {
+    /// Largest value that fits in the 18 bits of a PGN.
+    pub const MAX: u32 = 0x3FFFF;
+
+    /// Create a new `Pgn` from its raw 18-bit value.
+    ///
+    /// Returns `None` for PDU1 values with a non-zero PDU specific field, use
+    /// [`from_parts`](Self::from_parts) to mask it out.
+    pub const fn new(raw: u32) -> Option<Self> {
+        let pdu1 = ((raw >> 8) as u8) < 240;
+
+        if raw > Self::MAX || (pdu1 && raw & 0xFF != 0) {
+            None
+        } else {
+            Some(Self(raw))
+        }
+    }
+
+    /// Create a `Pgn` from its individual fields.
+    ///
+    /// The PDU specific field is masked out for PDU1 formats.
+    pub const fn from_parts(
+        extended_data_page: bool,
+        data_page: bool,
+        pdu_format: u8,
+        pdu_specific: u8,
+    ) -> Self {
+        let pdu_specific = if pdu_format < 240 { 0 } else { pdu_specific };
+
+        Self(
+            (extended_data_page as u32) << 17
+                | (data_page as u32) << 16
+                | (pdu_format as u32) << 8
+                | pdu_specific as u32,
+        )
+    }
+
+    /// Returns the raw 18-bit value.
+    pub const fn as_raw(&self) -> u32 {
+        self.0
+    }
+
+    /// PDU specific.
+    ///
+    /// For PDU1 groups this is usually zero, for PDU2 groups it is the group
+    /// extension.
+    pub const fn pdu_specific(&self) -> u8 {
+        (self.0 & 0xFF) as u8
+    }
+
+    /// PDU format.
+    pub const fn pdu_format(&self) -> u8 {
+        ((self.0 >> 8) & 0xFF) as u8
+    }
+
+    /// Data page.
+    pub const fn data_page(&self) -> bool {
+        ((self.0 >> 16) & 1) == 1
+    }
+
+    /// Extended data page.
+    pub const fn extended_data_page(&self) -> bool {
+        ((self.0 >> 17) & 1) == 1
+    }
+
+    /// PDU1 format, the message is sent to a specific destination address.
+    pub const fn is_pdu1(&self) -> bool {
+        self.pdu_format() < 240
+    }
+
+    /// PDU2 format, the message is broadcast and the PDU specific field is
+    /// the group extension.
+    pub const fn is_pdu2(&self) -> bool {
+        !self.is_pdu1()
+    }
+}
+
+impl From<Pgn> for u32 {
+    fn from(value: Pgn) -> u32 {
+        value.0
+    }
+}
+
 /// Extended 29-bit identifier.
 pub trait Extended {
+    /// Parameter group number.
+    fn pgn(&self) -> Pgn;
+
+    /// PDU1 format (destination specific).
+    fn is_pdu1(&self) -> bool;
+
+    /// PDU2 format (broadcast).
+    fn is_pdu2(&self) -> bool;
+
     /// Destination address.
-    fn destination_address(&self) -> Option<u8>;
+    ///
+    /// Only present for PDU1 format identifiers.
+    fn destination_address(&self) -> Option<Address>;
 
     /// Group extension.
+    ///
+    /// Only present for PDU2 format identifiers.
     fn group_extension(&self) -> Option<u8>;
 
     /// PDU specific.
@@ -67,21 +243,36 @@ pub trait Extended {
 }
 
 impl Extended for ExtendedId {
-    fn destination_address(&self) -> Option<u8> {
-        let value = self.pdu_format();
+    fn pgn(&self) -> Pgn {
+        Pgn::from_parts(
+            self.extended_data_page(),
+            self.data_page(),
+            self.pdu_format(),
+            self.pdu_specific(),
+        )
+    }
 
-        match value {
-            0..=239 => Some(value),
-            240..=255 => None,
+    fn is_pdu1(&self) -> bool {
+        self.pdu_format() < 240
+    }
+
+    fn is_pdu2(&self) -> bool {
+        !self.is_pdu1()
+    }
+
+    fn destination_address(&self) -> Option<Address> {
+        if self.is_pdu1() {
+            Some(Address(self.pdu_specific()))
+        } else {
+            None
         }
     }
 
     fn group_extension(&self) -> Option<u8> {
-        let value = self.pdu_format();
-
-        match value {
-            0..=239 => None,
-            240..=255 => Some(value),
+        if self.is_pdu2() {
+            Some(self.pdu_specific())
+        } else {
+            None
         }
     }
 
@@ -102,9 +293,137 @@ impl Extended for ExtendedId {
     }
 }
 
+/// J1939 identifier.
+///
+/// A validated set of priority, PGN and addressing that can be converted into
+/// an [`ExtendedId`] for transmission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct J1939Id {
+    priority: u8,
+    pgn: Pgn,
+    destination_address: Address,
+    source_address: Address,
+}
+
+impl J1939Id {
+    /// Lowest (numerically largest) priority.
+    pub const MAX_PRIORITY: u8 = 7;
+
+    /// Create a new `J1939Id`.
+    ///
+    /// PDU2 parameter groups are always broadcast, so `destination_address`
+    /// must be [`Address::GLOBAL`].
+    pub const fn new(
+        priority: u8,
+        pgn: Pgn,
+        destination_address: Address,
+        source_address: Address,
+    ) -> Result<Self, IdError> {
+        if priority > Self::MAX_PRIORITY {
+            return Err(IdError::InvalidPriority);
+        }
+
+        if pgn.extended_data_page() && pgn.data_page() {
+            return Err(IdError::ReservedDataPage);
+        }
+
+        // PDU1 PGNs have a zero PDU specific field by construction
+        if pgn.is_pdu2() && !destination_address.is_global() {
+            return Err(IdError::DestinationNotGlobal);
+        }
+
+        Ok(Self {
+            priority,
+            pgn,
+            destination_address,
+            source_address,
+        })
+    }
+
+    /// Create a new `J1939Id` for a broadcast to all nodes.
+    pub const fn broadcast(
+        priority: u8,
+        pgn: Pgn,
+        source_address: Address,
+    ) -> Result<Self, IdError> {
+        Self::new(priority, pgn, Address::GLOBAL, source_address)
+    }
+
+    /// Message priority.
+    pub const fn priority(&self) -> u8 {
+        self.priority
+    }
+
+    /// Parameter group number.
+    pub const fn pgn(&self) -> Pgn {
+        self.pgn
+    }
+
+    /// Destination address, global for PDU2 parameter groups.
+    pub const fn destination_address(&self) -> Address {
+        self.destination_address
+    }
+
+    /// Message source address.
+    pub const fn source_address(&self) -> Address {
+        self.source_address
+    }
+
+    /// Returns the raw 29-bit identifier value.
+    pub const fn as_raw(&self) -> u32 {
+        let pdu_specific = if self.pgn.is_pdu1() {
+            self.destination_address.0
+        } else {
+            self.pgn.pdu_specific()
+        };
+
+        (self.priority as u32) << 26
+            | (self.pgn.as_raw() & 0x3FF00) << 8
+            | (pdu_specific as u32) << 8
+            | self.source_address.0 as u32
+    }
+}
+
+impl From<J1939Id> for ExtendedId {
+    fn from(value: J1939Id) -> ExtendedId {
+        // as_raw never exceeds 29 bits
+        ExtendedId::new(value.as_raw()).unwrap()
+    }
+}
+
+impl From<J1939Id> for Id {
+    fn from(value: J1939Id) -> Id {
+        Id::Extended(value.into())
+    }
+}
+
+impl TryFrom<ExtendedId> for J1939Id {
+    type Error = IdError;
+
+    fn try_from(value: ExtendedId) -> Result<Self, Self::Error> {
+        J1939Id::new(
+            value.priority(),
+            value.pgn(),
+            value.destination_address().unwrap_or(Address::GLOBAL),
+            value.source_address(),
+        )
+    }
+}
+
+impl TryFrom<Id> for J1939Id {
+    type Error = IdError;
+
+    fn try_from(value: Id) -> Result<Self, Self::Error> {
+        match value {
+            Id::Standard(_) => Err(IdError::NotExtended),
+            Id::Extended(id) => J1939Id::try_from(id),
+        }
+    }
+}
+
 impl SourceAddress for ExtendedId {
-    fn source_address(&self) -> u8 {
-        (self.as_raw() & 0xFF) as u8
+    fn source_address(&self) -> Address {
+        Address((self.as_raw() & 0xFF) as u8)
     }
 }
 
@@ -136,7 +455,7 @@ mod tests {
     #[test]
     fn std_source_address() {
         let standard = StandardId::new(0x755).unwrap();
-        assert_eq!(standard.source_address(), 0x55);
+        assert_eq!(standard.source_address(), Address::new(0x55));
     }
 
     #[test]
@@ -148,7 +467,7 @@ mod tests {
     #[test]
     fn ext_source_address() {
         let extended = ExtendedId::new(0x7665544).unwrap();
-        assert_eq!(extended.source_address(), 0x44);
+        assert_eq!(extended.source_address(), Address::new(0x44));
     }
 
     #[test]
@@ -172,12 +491,157 @@ mod tests {
     #[test]
     fn ext_data_page() {
         let extended = ExtendedId::new(0x1FFF_FFFF).unwrap();
-        assert_eq!(extended.data_page(), true);
+        assert!(extended.data_page());
     }
 
     #[test]
     fn ext_extended_data_page() {
         let extended = ExtendedId::new(0x1FFF_FFFF).unwrap();
-        assert_eq!(extended.extended_data_page(), true);
+        assert!(extended.extended_data_page());
+    }
+
+    #[test]
+    fn ext_pdu1() {
+        // TSC1 from engine retarder (0x0F) to engine (0x00)
+        let extended = ExtendedId::new(0x0C00_000F).unwrap();
+        assert!(extended.is_pdu1());
+        assert!(!extended.is_pdu2());
+        assert_eq!(extended.destination_address(), Some(Address::new(0x00)));
+        assert_eq!(extended.group_extension(), None);
+        assert_eq!(extended.pgn().as_raw(), 0);
+
+        // request from 0xF9 to 0x00, see J1939-21 5.4.2
+        let extended = ExtendedId::new(0x18EA_00F9).unwrap();
+        assert_eq!(extended.destination_address(), Some(Address::new(0x00)));
+        assert_eq!(extended.pgn().as_raw(), 59904);
+
+        // global request, PS must not leak into the PGN
+        let extended = ExtendedId::new(0x18EA_FFF9).unwrap();
+        assert_eq!(extended.destination_address(), Some(Address::new(0xFF)));
+        assert_eq!(extended.pgn().as_raw(), 59904);
+    }
+
+    #[test]
+    fn ext_pdu2() {
+        // EEC1 from engine, see J1939-21 5.1.2
+        let extended = ExtendedId::new(0x0CF0_0400).unwrap();
+        assert!(extended.is_pdu2());
+        assert!(!extended.is_pdu1());
+        assert_eq!(extended.destination_address(), None);
+        assert_eq!(extended.group_extension(), Some(0x04));
+        assert_eq!(extended.pgn().as_raw(), 61444);
+
+        // DM1
+        let extended = ExtendedId::new(0x18FE_CA00).unwrap();
+        assert_eq!(extended.group_extension(), Some(0xCA));
+        assert_eq!(extended.pgn().as_raw(), 65226);
+    }
+
+    #[test]
+    fn ext_pgn_data_page() {
+        // Proprietary A2 lives on data page 1
+        let extended = ExtendedId::new(0x19EF_1234).unwrap();
+        assert_eq!(extended.pgn().as_raw(), 126720);
+        assert!(extended.pgn().data_page());
+        assert!(!extended.pgn().extended_data_page());
+
+        let extended = ExtendedId::new(0x1BFE_CA00).unwrap();
+        assert_eq!(extended.pgn().as_raw(), 0x3FECA);
+        assert!(extended.pgn().extended_data_page());
+    }
+
+    #[test]
+    fn pgn_fields() {
+        let pgn = Pgn::new(0xFECA).unwrap();
+        assert_eq!(pgn.pdu_format(), 0xFE);
+        assert_eq!(pgn.pdu_specific(), 0xCA);
+        assert!(pgn.is_pdu2());
+
+        let pgn = Pgn::new(0xEA00).unwrap();
+        assert!(pgn.is_pdu1());
+        assert_eq!(pgn, Pgn::from_parts(false, false, 0xEA, 0x55));
+
+        // PDU1 values are kept canonical
+        assert_eq!(Pgn::new(0xEA55), None);
+        assert_eq!(Pgn::new(0x1EF12), None);
+        assert_eq!(Pgn::new(0x1EF00).map(|pgn| pgn.data_page()), Some(true));
+
+        assert_eq!(Pgn::new(0x40000), None);
+    }
+
+    #[test]
+    fn j1939id_pdu1() {
+        let pgn = Pgn::new(59904).unwrap();
+        let id = J1939Id::new(6, pgn, Address::new(0x00), Address::new(0xF9))
+            .unwrap();
+        assert_eq!(ExtendedId::from(id).as_raw(), 0x18EA_00F9);
+
+        let id = J1939Id::new(
+            3,
+            Pgn::new(0).unwrap(),
+            Address::new(0x00),
+            Address::new(0x0F),
+        )
+        .unwrap();
+        assert_eq!(id.as_raw(), 0x0C00_000F);
+    }
+
+    #[test]
+    fn j1939id_pdu2() {
+        let id =
+            J1939Id::broadcast(3, Pgn::new(61444).unwrap(), Address::new(0x00))
+                .unwrap();
+        assert_eq!(id.as_raw(), 0x0CF0_0400);
+
+        let id = J1939Id::broadcast(
+            6,
+            Pgn::new(0x1EF00).unwrap(),
+            Address::new(0x34),
+        );
+        assert_eq!(id.unwrap().as_raw(), 0x19EF_FF34);
+    }
+
+    #[test]
+    fn j1939id_errors() {
+        let pgn = Pgn::new(61444).unwrap();
+        assert_eq!(
+            J1939Id::new(8, pgn, Address::GLOBAL, Address::new(0)),
+            Err(IdError::InvalidPriority)
+        );
+        assert_eq!(
+            J1939Id::new(3, pgn, Address::new(0x10), Address::new(0)),
+            Err(IdError::DestinationNotGlobal)
+        );
+
+        let pgn = Pgn::new(0x3FECA).unwrap();
+        assert_eq!(
+            J1939Id::broadcast(6, pgn, Address::new(0)),
+            Err(IdError::ReservedDataPage)
+        );
+    }
+
+    #[test]
+    fn j1939id_round_trip() {
+        let extended = ExtendedId::new(0x18EA_00F9).unwrap();
+        let id = J1939Id::try_from(extended).unwrap();
+        assert_eq!(id.priority(), 6);
+        assert_eq!(id.pgn().as_raw(), 59904);
+        assert_eq!(id.destination_address(), Address::new(0x00));
+        assert_eq!(id.source_address(), Address::new(0xF9));
+        assert_eq!(ExtendedId::from(id), extended);
+
+        let standard = Id::Standard(StandardId::new(0x755).unwrap());
+        assert_eq!(J1939Id::try_from(standard), Err(IdError::NotExtended));
+    }
+
+    #[test]
+    fn address_kinds() {
+        assert!(Address::GLOBAL.is_global());
+        assert!(!Address::GLOBAL.is_unicast());
+        assert!(Address::NULL.is_null());
+        assert!(!Address::NULL.is_unicast());
+        assert!(Address::new(253).is_unicast());
+        assert_eq!(Address::unicast(254), None);
+        assert_eq!(Address::unicast(0), Some(Address::new(0)));
     }
 }
@@ -7,12 +7,28 @@
 //! signal types. In time it will supports parsing and extracting signals from
 //! message bodies based on identifier metadata.
 
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+pub mod error;
+pub mod filter;
+pub mod frame;
+pub mod function;
 pub mod identifier;
 pub mod manufacturer;
+pub mod message;
 pub mod name;
+pub mod network;
+pub mod preferred_address;
 pub mod prelude;
+pub mod proprietary;
 pub mod signal;
 pub mod slot;
+pub mod time;
+pub mod transport;
+pub mod vehicle_system;
+
+#[cfg(test)]
+mod testing;
 
 #[cfg(feature = "float-32")]
 pub type Float = f32;
@@ -1,63 +1,774 @@
-use crate::identifier::Extended;
-use embedded_can::ExtendedId;
+//! Application layer messages.
+//!
+//! Decoding works on views over the received payload bytes, encoding returns
+//! the payload ready to be placed in a frame.
 
-enum MessageError {
-    TooManyControlBytes,
-}
+use crate::error::MessageError;
+use crate::frame::J1939Frame;
+use crate::identifier::{Address, Extended, J1939Id, Pgn, SourceAddress};
+use crate::proprietary::{Proprietary, ProprietaryKind};
+use embedded_can::{ExtendedId, Frame};
 
-enum Message<'a> {
-    Command {},
-    Request {
-        source: u8,
-        format: u8,
-        destination: u8,
-    },
-    Response {},
+/// Decoded message.
+pub enum Message<'a> {
+    Request(Request),
+    Request2(Request2),
+    Transfer(Transfer<'a>),
+    Proprietary(Proprietary<'a>),
     Ack(Ack<'a>),
-    Group {},
 }
 
-enum AckKind {
+impl<'a> Message<'a> {
+    /// Decode a message from its identifier and payload.
+    ///
+    /// Returns [`MessageError::UnexpectedPgn`] for parameter groups that
+    /// can't be decoded yet.
+    pub fn decode(
+        id: ExtendedId,
+        data: &'a [u8],
+    ) -> Result<Self, MessageError> {
+        match id.pgn() {
+            Request::PGN => Request::decode(id, data).map(Message::Request),
+            Request2::PGN => Request2::decode(id, data).map(Message::Request2),
+            Transfer::PGN => Transfer::decode(id, data).map(Message::Transfer),
+            Ack::PGN => Ack::new(data).map(Message::Ack),
+            pgn if ProprietaryKind::from_pgn(pgn).is_some() => {
+                Proprietary::decode(id, data).map(Message::Proprietary)
+            }
+            _ => Err(MessageError::UnexpectedPgn),
+        }
+    }
+
+    /// Decode a message from a received frame.
+    pub fn from_frame<F: Frame>(
+        frame: J1939Frame<'a, F>,
+    ) -> Result<Self, MessageError> {
+        Self::decode(frame.id(), frame.data())
+    }
+}
+
+/// Acknowledgement control byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AckKind {
     /// Positive acknowledgement.
-    Positive,
+    Positive = 0,
     /// Negative acknowledgement.
-    Negative,
+    Negative = 1,
     /// Accesss denied.
-    AccessDenied,
+    AccessDenied = 2,
     /// Cannot respond.
-    CannotRespond,
-    /// Don't care/take no action.
-    NoAction,
+    CannotRespond = 3,
 }
 
-struct Ack<'a> {
-    kind: AckKind,
-    control_byte: u8,
-    group_function_value: &'a [u8],
-    address_acknowledged: u8,
-    pgn_of_request: u32,
+impl TryFrom<u8> for AckKind {
+    type Error = MessageError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(AckKind::Positive),
+            1 => Ok(AckKind::Negative),
+            2 => Ok(AckKind::AccessDenied),
+            3 => Ok(AckKind::CannotRespond),
+            _ => Err(MessageError::InvalidControlByte),
+        }
+    }
 }
 
+/// Acknowledgement (PGN 59392).
+///
+/// See SAEJ1939-21 5.4.4
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ack<'a>(&'a [u8]);
+
 impl<'a> Ack<'a> {
-    fn new_positive(
-        address_acknowledged: u8,
-        pgn_of_request: u32,
-        group_function_value: &'a [u8],
+    /// Acknowledgement parameter group number.
+    pub const PGN: Pgn = Pgn::from_parts(false, false, 0xE8, 0);
+
+    /// Payload length in bytes.
+    pub const LEN: usize = 8;
+
+    /// Create a view over an acknowledgement payload.
+    pub fn new(data: &'a [u8]) -> Result<Self, MessageError> {
+        if data.len() != Self::LEN {
+            return Err(MessageError::InvalidLength);
+        }
+
+        AckKind::try_from(data[0])?;
+        read_pgn(&data[5..8])?;
+
+        Ok(Self(data))
+    }
+
+    /// Encode an acknowledgement payload.
+    ///
+    /// `group_function_value` is only used when acknowledging a group
+    /// function, otherwise it is sent as not available.
+    pub fn encode(
+        kind: AckKind,
+        group_function_value: Option<u8>,
+        address_acknowledged: Address,
+        pgn: Pgn,
+    ) -> [u8; 8] {
+        let pgn = write_pgn(pgn);
+
+        [
+            kind as u8,
+            group_function_value.unwrap_or(0xFF),
+            0xFF,
+            0xFF,
+            address_acknowledged.as_raw(),
+            pgn[0],
+            pgn[1],
+            pgn[2],
+        ]
+    }
+
+    /// Kind of acknowledgement.
+    pub fn kind(&self) -> AckKind {
+        // checked in `new`
+        AckKind::try_from(self.0[0]).unwrap()
+    }
+
+    /// Group function value being acknowledged, if any.
+    pub fn group_function_value(&self) -> Option<u8> {
+        match self.0[1] {
+            0xFF => None,
+            value => Some(value),
+        }
+    }
+
+    /// Address of the node the acknowledgement is for.
+    pub fn address_acknowledged(&self) -> Address {
+        Address::new(self.0[4])
+    }
+
+    /// Parameter group number being acknowledged.
+    pub fn pgn(&self) -> Pgn {
+        // checked in `new`
+        read_pgn(&self.0[5..8]).unwrap()
+    }
+}
+
+/// Request (PGN 59904).
+///
+/// Asks one node, or all nodes when sent to the global address, to transmit
+/// a parameter group.
+///
+/// See SAEJ1939-21 5.4.2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Request {
+    pgn: Pgn,
+    destination_address: Address,
+    source_address: Address,
+}
+
+impl Request {
+    /// Request parameter group number.
+    pub const PGN: Pgn = Pgn::from_parts(false, false, 0xEA, 0);
+
+    /// Payload length in bytes.
+    pub const LEN: usize = 3;
+
+    /// Default priority for requests.
+    pub const PRIORITY: u8 = 6;
+
+    /// Create a new request for `pgn`.
+    ///
+    /// Use [`Address::GLOBAL`] as the destination to request from all nodes.
+    pub fn new(
+        pgn: Pgn,
+        destination_address: Address,
+        source_address: Address,
+    ) -> Self {
+        Self {
+            pgn,
+            destination_address,
+            source_address,
+        }
+    }
+
+    /// Decode a request from its identifier and payload.
+    ///
+    /// The payload must be exactly 3 bytes long, requests of any other
+    /// length are rejected.
+    pub fn decode(id: ExtendedId, data: &[u8]) -> Result<Self, MessageError> {
+        if id.pgn() != Self::PGN {
+            return Err(MessageError::UnexpectedPgn);
+        }
+
+        if data.len() != Self::LEN {
+            return Err(MessageError::InvalidLength);
+        }
+
+        Ok(Self {
+            pgn: read_pgn(data)?,
+            // PDU1 identifiers always have a destination
+            destination_address: id.destination_address().unwrap(),
+            source_address: id.source_address(),
+        })
+    }
+
+    /// Decode a request from a received frame.
+    pub fn from_frame<F: Frame>(frame: &F) -> Result<Self, MessageError> {
+        let frame = J1939Frame::new(frame)?;
+
+        Self::decode(frame.id(), frame.data())
+    }
+
+    /// Encode the request payload.
+    pub fn encode(&self) -> [u8; 3] {
+        write_pgn(self.pgn)
+    }
+
+    /// Identifier to send the request with.
+    pub fn id(&self) -> J1939Id {
+        // request is PDU1 and default priority is in range
+        J1939Id::new(
+            Self::PRIORITY,
+            Self::PGN,
+            self.destination_address,
+            self.source_address,
+        )
+        .unwrap()
+    }
+
+    /// Create a frame carrying the request.
+    pub fn to_frame<F: Frame>(&self) -> Option<F> {
+        F::new(self.id(), &self.encode())
+    }
+
+    /// Parameter group number being requested.
+    pub fn pgn(&self) -> Pgn {
+        self.pgn
+    }
+
+    /// Node the request is sent to.
+    pub fn destination_address(&self) -> Address {
+        self.destination_address
+    }
+
+    /// Node sending the request.
+    pub fn source_address(&self) -> Address {
+        self.source_address
+    }
+
+    /// Request is sent to all nodes.
+    pub fn is_global(&self) -> bool {
+        self.destination_address.is_global()
+    }
+}
+
+/// Request2 (PGN 51456).
+///
+/// Like [`Request`] but with extended identifier bytes that select which
+/// instance of the parameter group is wanted, and a flag asking for the
+/// response to be sent using the [`Transfer`] parameter group.
+///
+/// See SAEJ1939-21 5.4.6
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Request2 {
+    pgn: Pgn,
+    extended_identifier: [u8; 3],
+    use_transfer: bool,
+    destination_address: Address,
+    source_address: Address,
+}
+
+/// How to answer a [`Request2`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request2Response {
+    /// Send the requested parameter group as normal.
+    Direct,
+    /// Wrap the requested parameter group in a [`Transfer`] message.
+    Transfer,
+}
+
+impl Request2 {
+    /// Request2 parameter group number.
+    pub const PGN: Pgn = Pgn::from_parts(false, false, 0xC9, 0);
+
+    /// Payload length in bytes.
+    pub const LEN: usize = 8;
+
+    /// Default priority for requests.
+    pub const PRIORITY: u8 = 6;
+
+    /// Create a new request for `pgn`.
+    pub fn new(
+        pgn: Pgn,
+        extended_identifier: [u8; 3],
+        use_transfer: bool,
+        destination_address: Address,
+        source_address: Address,
+    ) -> Self {
+        Self {
+            pgn,
+            extended_identifier,
+            use_transfer,
+            destination_address,
+            source_address,
+        }
+    }
+
+    /// Decode a request from its identifier and payload.
+    pub fn decode(id: ExtendedId, data: &[u8]) -> Result<Self, MessageError> {
+        if id.pgn() != Self::PGN {
+            return Err(MessageError::UnexpectedPgn);
+        }
+
+        if data.len() != Self::LEN {
+            return Err(MessageError::InvalidLength);
+        }
+
+        Ok(Self {
+            pgn: read_pgn(&data[0..3])?,
+            extended_identifier: [data[3], data[4], data[5]],
+            use_transfer: data[6] & 0b11 == 0b01,
+            // PDU1 identifiers always have a destination
+            destination_address: id.destination_address().unwrap(),
+            source_address: id.source_address(),
+        })
+    }
+
+    /// Decode a request from a received frame.
+    pub fn from_frame<F: Frame>(frame: &F) -> Result<Self, MessageError> {
+        let frame = J1939Frame::new(frame)?;
+
+        Self::decode(frame.id(), frame.data())
+    }
+
+    /// Encode the request payload.
+    pub fn encode(&self) -> [u8; 8] {
+        let pgn = write_pgn(self.pgn);
+
+        [
+            pgn[0],
+            pgn[1],
+            pgn[2],
+            self.extended_identifier[0],
+            self.extended_identifier[1],
+            self.extended_identifier[2],
+            0b1111_1100 | self.use_transfer as u8,
+            0xFF,
+        ]
+    }
+
+    /// Identifier to send the request with.
+    pub fn id(&self) -> J1939Id {
+        // request is PDU1 and default priority is in range
+        J1939Id::new(
+            Self::PRIORITY,
+            Self::PGN,
+            self.destination_address,
+            self.source_address,
+        )
+        .unwrap()
+    }
+
+    /// Create a frame carrying the request.
+    pub fn to_frame<F: Frame>(&self) -> Option<F> {
+        F::new(self.id(), &self.encode())
+    }
+
+    /// Parameter group number being requested.
+    pub fn pgn(&self) -> Pgn {
+        self.pgn
+    }
+
+    /// Extended identifier bytes selecting the requested data.
+    pub fn extended_identifier(&self) -> [u8; 3] {
+        self.extended_identifier
+    }
+
+    /// Response is wanted using the transfer parameter group.
+    pub fn use_transfer(&self) -> bool {
+        self.use_transfer
+    }
+
+    /// Node the request is sent to.
+    pub fn destination_address(&self) -> Address {
+        self.destination_address
+    }
+
+    /// Node sending the request.
+    pub fn source_address(&self) -> Address {
+        self.source_address
+    }
+
+    /// Decide how the request should be answered.
+    pub fn response(&self) -> Request2Response {
+        if self.use_transfer {
+            Request2Response::Transfer
+        } else {
+            Request2Response::Direct
+        }
+    }
+
+    /// Create the [`Transfer`] message answering this request.
+    ///
+    /// `source_address` is the address of the responding node.
+    pub fn transfer<'a>(
+        &self,
+        source_address: Address,
+        data: &'a [u8],
+    ) -> Result<Transfer<'a>, MessageError> {
+        Transfer::new(
+            self.pgn,
+            self.extended_identifier,
+            data,
+            self.source_address,
+            source_address,
+        )
+    }
+}
+
+/// Transfer (PGN 51712).
+///
+/// Carries a parameter group requested with [`Request2`]. Messages longer
+/// than 8 bytes need to be sent using the transport protocol.
+///
+/// See SAEJ1939-21 5.4.7
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transfer<'a> {
+    pgn: Pgn,
+    extended_identifier: [u8; 3],
+    data: &'a [u8],
+    destination_address: Address,
+    source_address: Address,
+}
+
+impl<'a> Transfer<'a> {
+    /// Transfer parameter group number.
+    pub const PGN: Pgn = Pgn::from_parts(false, false, 0xCA, 0);
+
+    /// Length of the header before the transferred data.
+    pub const HEADER_LEN: usize = 7;
+
+    /// Largest amount of data that can be transferred.
+    pub const MAX_DATA_LEN: usize = 255;
+
+    /// Default priority for transfers.
+    pub const PRIORITY: u8 = 6;
+
+    /// Create a new transfer of `data`.
+    pub fn new(
+        pgn: Pgn,
+        extended_identifier: [u8; 3],
+        data: &'a [u8],
+        destination_address: Address,
+        source_address: Address,
     ) -> Result<Self, MessageError> {
-        let control_byte = match group_function_value.len() {
-            0 => 0,
-            1 => 128,
-            2 => 144,
-            3 => 160,
-            _ => return Err(MessageError::TooManyControlBytes),
-        };
+        if data.len() > Self::MAX_DATA_LEN {
+            return Err(MessageError::InvalidLength);
+        }
 
         Ok(Self {
-            kind: AckKind::Positive,
-            control_byte,
-            group_function_value,
-            address_acknowledged,
-            pgn_of_request,
+            pgn,
+            extended_identifier,
+            data,
+            destination_address,
+            source_address,
         })
     }
+
+    /// Decode a transfer from its identifier and payload.
+    pub fn decode(
+        id: ExtendedId,
+        data: &'a [u8],
+    ) -> Result<Self, MessageError> {
+        if id.pgn() != Self::PGN {
+            return Err(MessageError::UnexpectedPgn);
+        }
+
+        if data.len() < Self::HEADER_LEN {
+            return Err(MessageError::InvalidLength);
+        }
+
+        let len = data[6] as usize;
+        let data_end = Self::HEADER_LEN + len;
+
+        if data.len() < data_end {
+            return Err(MessageError::InvalidLength);
+        }
+
+        Ok(Self {
+            pgn: read_pgn(&data[0..3])?,
+            extended_identifier: [data[3], data[4], data[5]],
+            data: &data[Self::HEADER_LEN..data_end],
+            // PDU1 identifiers always have a destination
+            destination_address: id.destination_address().unwrap(),
+            source_address: id.source_address(),
+        })
+    }
+
+    /// Encode the transfer payload into `buffer`.
+    ///
+    /// Returns the number of bytes written.
+    pub fn encode(&self, buffer: &mut [u8]) -> Result<usize, MessageError> {
+        let len = Self::HEADER_LEN + self.data.len();
+
+        if buffer.len() < len {
+            return Err(MessageError::BufferTooSmall);
+        }
+
+        buffer[0..3].copy_from_slice(&write_pgn(self.pgn));
+        buffer[3..6].copy_from_slice(&self.extended_identifier);
+        buffer[6] = self.data.len() as u8;
+        buffer[Self::HEADER_LEN..len].copy_from_slice(self.data);
+
+        Ok(len)
+    }
+
+    /// Identifier to send the transfer with.
+    pub fn id(&self) -> J1939Id {
+        // transfer is PDU1 and default priority is in range
+        J1939Id::new(
+            Self::PRIORITY,
+            Self::PGN,
+            self.destination_address,
+            self.source_address,
+        )
+        .unwrap()
+    }
+
+    /// Parameter group number being transferred.
+    pub fn pgn(&self) -> Pgn {
+        self.pgn
+    }
+
+    /// Extended identifier bytes from the request.
+    pub fn extended_identifier(&self) -> [u8; 3] {
+        self.extended_identifier
+    }
+
+    /// Transferred parameter group data.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Node the transfer is sent to.
+    pub fn destination_address(&self) -> Address {
+        self.destination_address
+    }
+
+    /// Node sending the transfer.
+    pub fn source_address(&self) -> Address {
+        self.source_address
+    }
+}
+
+/// Writes a little endian 24-bit parameter group number.
+fn write_pgn(pgn: Pgn) -> [u8; 3] {
+    let pgn = pgn.as_raw().to_le_bytes();
+
+    [pgn[0], pgn[1], pgn[2]]
+}
+
+/// Reads a little endian 24-bit parameter group number.
+fn read_pgn(data: &[u8]) -> Result<Pgn, MessageError> {
+    Pgn::new(u32::from_le_bytes([data[0], data[1], data[2], 0]))
+        .ok_or(MessageError::InvalidPgn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestFrame;
+    use embedded_can::StandardId;
+
+    #[test]
+    fn ack_decode() {
+        // NACK of a request for component identification from 0xF9
+        let data = [0x01, 0xFF, 0xFF, 0xFF, 0xF9, 0xEB, 0xFE, 0x00];
+        let ack = Ack::new(&data).unwrap();
+
+        assert_eq!(ack.kind(), AckKind::Negative);
+        assert_eq!(ack.group_function_value(), None);
+        assert_eq!(ack.address_acknowledged(), Address::new(0xF9));
+        assert_eq!(ack.pgn(), Pgn::new(65259).unwrap());
+    }
+
+    #[test]
+    fn ack_round_trip() {
+        let kinds = [
+            AckKind::Positive,
+            AckKind::Negative,
+            AckKind::AccessDenied,
+            AckKind::CannotRespond,
+        ];
+
+        for kind in kinds {
+            let pgn = Pgn::new(0x1EF00).unwrap();
+            let data = Ack::encode(kind, Some(0x12), Address::new(0x21), pgn);
+            assert_eq!(data, [kind as u8, 0x12, 0xFF, 0xFF, 0x21, 0, 0xEF, 1]);
+
+            let ack = Ack::new(&data).unwrap();
+            assert_eq!(ack.kind(), kind);
+            assert_eq!(ack.group_function_value(), Some(0x12));
+            assert_eq!(ack.address_acknowledged(), Address::new(0x21));
+            assert_eq!(ack.pgn(), pgn);
+        }
+    }
+
+    #[test]
+    fn ack_errors() {
+        let data = [0x04, 0xFF, 0xFF, 0xFF, 0xF9, 0xEB, 0xFE, 0x00];
+        assert_eq!(Ack::new(&data), Err(MessageError::InvalidControlByte));
+
+        let data = [0x00, 0xFF, 0xFF, 0xFF, 0xF9, 0xEB, 0xFE, 0x04];
+        assert_eq!(Ack::new(&data), Err(MessageError::InvalidPgn));
+
+        assert_eq!(Ack::new(&data[..7]), Err(MessageError::InvalidLength));
+    }
+
+    #[test]
+    fn request_decode() {
+        // global request for component identification from 0xF9
+        let id = ExtendedId::new(0x18EA_FFF9).unwrap();
+        let request = Request::decode(id, &[0xEB, 0xFE, 0x00]).unwrap();
+
+        assert_eq!(request.pgn(), Pgn::new(65259).unwrap());
+        assert_eq!(request.destination_address(), Address::GLOBAL);
+        assert_eq!(request.source_address(), Address::new(0xF9));
+        assert!(request.is_global());
+
+        // DM2 from the engine
+        let id = ExtendedId::new(0x18EA_00F9).unwrap();
+        let request = Request::decode(id, &[0xCB, 0xFE, 0x00]).unwrap();
+
+        assert_eq!(request.pgn(), Pgn::new(65227).unwrap());
+        assert_eq!(request.destination_address(), Address::new(0x00));
+        assert!(!request.is_global());
+    }
+
+    #[test]
+    fn request_round_trip() {
+        let pgn = Pgn::new(65259).unwrap();
+        let request = Request::new(pgn, Address::new(0x00), Address::new(0xF9));
+
+        assert_eq!(request.encode(), [0xEB, 0xFE, 0x00]);
+        assert_eq!(request.id().as_raw(), 0x18EA_00F9);
+
+        let frame: TestFrame = request.to_frame().unwrap();
+        assert_eq!(frame.dlc(), 3);
+        assert_eq!(Request::from_frame(&frame), Ok(request));
+    }
+
+    #[test]
+    fn request_errors() {
+        let id = ExtendedId::new(0x18EA_FFF9).unwrap();
+        assert_eq!(
+            Request::decode(id, &[0xEB, 0xFE]),
+            Err(MessageError::InvalidLength)
+        );
+        assert_eq!(
+            Request::decode(id, &[0xEB, 0xFE, 0x00, 0xFF, 0xFF]),
+            Err(MessageError::InvalidLength)
+        );
+
+        let id = ExtendedId::new(0x18E8_FFF9).unwrap();
+        assert_eq!(
+            Request::decode(id, &[0xEB, 0xFE, 0x00]),
+            Err(MessageError::UnexpectedPgn)
+        );
+
+        let frame = TestFrame::new(StandardId::ZERO, &[0xEB, 0xFE, 0x00]);
+        assert_eq!(
+            Request::from_frame(&frame.unwrap()),
+            Err(MessageError::InvalidFrame)
+        );
+    }
+
+    #[test]
+    fn message_decode() {
+        let id = ExtendedId::new(0x18EA_FFF9).unwrap();
+        let message = Message::decode(id, &[0xEB, 0xFE, 0x00]).unwrap();
+        assert!(matches!(message, Message::Request(_)));
+
+        let id = ExtendedId::new(0x18E8_FFF9).unwrap();
+        let data = [0x01, 0xFF, 0xFF, 0xFF, 0xF9, 0xEB, 0xFE, 0x00];
+        let message = Message::decode(id, &data).unwrap();
+        assert!(matches!(message, Message::Ack(_)));
+
+        let id = ExtendedId::new(0x18FF_10F9).unwrap();
+        let message = Message::decode(id, &data).unwrap();
+        assert!(matches!(message, Message::Proprietary(_)));
+
+        let frame = TestFrame::new(id, &data).unwrap();
+        let message = Message::from_frame(J1939Frame::new(&frame).unwrap());
+        assert!(matches!(message, Ok(Message::Proprietary(_))));
+    }
+
+    #[test]
+    fn request2_round_trip() {
+        let pgn = Pgn::new(65259).unwrap();
+        let request = Request2::new(
+            pgn,
+            [0x01, 0x02, 0x03],
+            true,
+            Address::new(0x00),
+            Address::new(0xF9),
+        );
+
+        let data = request.encode();
+        assert_eq!(data, [0xEB, 0xFE, 0x00, 0x01, 0x02, 0x03, 0xFD, 0xFF]);
+        assert_eq!(request.id().as_raw(), 0x18C9_00F9);
+        assert_eq!(request.response(), Request2Response::Transfer);
+
+        let frame: TestFrame = request.to_frame().unwrap();
+        assert_eq!(Request2::from_frame(&frame), Ok(request));
+
+        let id = ExtendedId::new(0x18C9_00F9).unwrap();
+        let data = [0xEB, 0xFE, 0x00, 0xFF, 0xFF, 0xFF, 0xFC, 0xFF];
+        let request = Request2::decode(id, &data).unwrap();
+        assert!(!request.use_transfer());
+        assert_eq!(request.response(), Request2Response::Direct);
+
+        // not available is treated as not required
+        let data = [0xEB, 0xFE, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+        let request = Request2::decode(id, &data).unwrap();
+        assert!(!request.use_transfer());
+
+        assert_eq!(
+            Request2::decode(id, &data[..3]),
+            Err(MessageError::InvalidLength)
+        );
+    }
+
+    #[test]
+    fn transfer_round_trip() {
+        let pgn = Pgn::new(65259).unwrap();
+        let request = Request2::new(
+            pgn,
+            [0x01, 0x02, 0x03],
+            true,
+            Address::new(0x00),
+            Address::new(0xF9),
+        );
+
+        let transfer = request.transfer(Address::new(0x00), b"ABC*").unwrap();
+        assert_eq!(transfer.id().as_raw(), 0x18CA_F900);
+
+        let mut buffer = [0; 16];
+        let len = transfer.encode(&mut buffer).unwrap();
+        assert_eq!(
+            &buffer[..len],
+            &[0xEB, 0xFE, 0x00, 0x01, 0x02, 0x03, 4, b'A', b'B', b'C', b'*']
+        );
+
+        let id = ExtendedId::new(0x18CA_F900).unwrap();
+        let decoded = Transfer::decode(id, &buffer[..len]).unwrap();
+        assert_eq!(decoded, transfer);
+        assert_eq!(decoded.data(), b"ABC*");
+
+        assert_eq!(
+            transfer.encode(&mut buffer[..8]),
+            Err(MessageError::BufferTooSmall)
+        );
+        assert_eq!(
+            Transfer::decode(id, &buffer[..len - 1]),
+            Err(MessageError::InvalidLength)
+        );
+    }
 }
@@ -1,11 +1,12 @@
 //! Network management.
 
+use crate::error::NameError;
 use bitfield::bitfield;
 bitfield! {
     /// NAME Bitfield
     ///
     /// See SAEJ1939-81 4.2.1.1
-    #[derive(Debug, Clone, Copy)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     struct Fields(u64);
     /// Identity Number.
     #[inline]
@@ -24,7 +25,7 @@ bitfield! {
     u8, function, set_function: 47, 40;
     /// Vehicle System Field.
     #[inline]
-    u8, vehicle_system, set_vehicle_system: 56, 49;
+    u8, vehicle_system, set_vehicle_system: 55, 49;
     /// Vehicle System Instance.
     #[inline]
     u8, vehicle_system_instance, set_vehicle_system_instance: 59, 56;
@@ -37,11 +38,12 @@ bitfield! {
 }
 
 /// NAME Definition
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Name(Fields);
 
 impl Name {
     /// Create a new `Name` instance.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         identity: u32,
         manufacturer_code: u16,
@@ -52,66 +54,74 @@ impl Name {
         vehicle_system_instance: u8,
         industry_group: IndustryGroup,
         arbitrary_address_capable: bool,
-    ) -> Option<Self> {
+    ) -> Result<Self, NameError> {
         let mut fields = Fields(0);
 
         if identity < 2u32.pow(21) {
             fields.set_identity(identity)
         } else {
-            return None;
+            return Err(NameError::Identity);
         }
 
         if manufacturer_code < 2u16.pow(11) {
             fields.set_manufacturer_code(manufacturer_code)
         } else {
-            return None;
+            return Err(NameError::ManufacturerCode);
         }
 
         if ecu_instance < 2u8.pow(3) {
             fields.set_ecu_instance(ecu_instance)
         } else {
-            return None;
+            return Err(NameError::EcuInstance);
         }
 
         if function_instance < 2u8.pow(5) {
             fields.set_function_instance(function_instance)
         } else {
-            return None;
+            return Err(NameError::FunctionInstance);
         }
 
-        if function < 2u8.pow(8) {
-            fields.set_function(function)
-        } else {
-            return None;
-        }
+        // function uses the full 8 bits
+        fields.set_function(function);
 
         if vehicle_system < 2u8.pow(7) {
             fields.set_vehicle_system(vehicle_system)
         } else {
-            return None;
+            return Err(NameError::VehicleSystem);
         }
 
         if vehicle_system_instance < 2u8.pow(4) {
             fields.set_vehicle_system_instance(vehicle_system_instance)
         } else {
-            return None;
+            return Err(NameError::VehicleSystemInstance);
         }
 
         let industry_group = industry_group as u8;
         if industry_group < 2u8.pow(3) {
             fields.set_industry_group(industry_group)
         } else {
-            return None;
+            return Err(NameError::IndustryGroup);
         }
 
         fields.set_arbitrary_address_capable(arbitrary_address_capable as u8);
 
-        Some(Self(fields))
+        Ok(Self(fields))
+    }
+
+    /// Create a `Name` from the little-endian bytes it is sent as.
+    pub const fn from_raw(raw: [u8; 8]) -> Self {
+        Self(Fields(u64::from_le_bytes(raw)))
     }
 
-    /// Returns the bytes of the NAME data in platform native byte order.
-    pub fn as_raw(&self) -> [u8; 8] {
-        self.0 .0.to_ne_bytes()
+    /// Returns the bytes of the NAME in the little-endian order it is sent
+    /// in.
+    pub const fn as_raw(&self) -> [u8; 8] {
+        self.0 .0.to_le_bytes()
+    }
+
+    /// The 64-bit value of the NAME, lower values have higher priority.
+    pub(crate) const fn to_bits(self) -> u64 {
+        self.0 .0
     }
 
     /// A 21-bit field assigned by the manufacturer and should be unique across units.
@@ -164,8 +174,22 @@ impl Name {
     }
 }
 
+/// NAMEs are ordered by their 64-bit value. When two controller applications
+/// claim the same address the one with the lowest NAME keeps it.
+impl Ord for Name {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.to_bits().cmp(&other.to_bits())
+    }
+}
+
+impl PartialOrd for Name {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 /// Industry group assignment.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum IndustryGroup {
     /// Global, applies to all.
@@ -185,7 +209,7 @@ pub enum IndustryGroup {
 }
 
 impl TryFrom<u8> for IndustryGroup {
-    type Error = ();
+    type Error = NameError;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
@@ -203,7 +227,7 @@ impl TryFrom<u8> for IndustryGroup {
             x if x == IndustryGroup::IndustrialProcess as u8 => {
                 Ok(IndustryGroup::IndustrialProcess)
             }
-            _ => Err(()),
+            _ => Err(NameError::IndustryGroup),
         }
     }
 }
@@ -213,10 +237,77 @@ mod tests {
     use super::*;
 
     // example taken from J1939-81 4.2.1.1
-    const EXAMPLE: Fields = Fields(u64::from_le_bytes([
+    const EXAMPLE_RAW: [u8; 8] = [
         0b00001110, 0b10011001, 0b10100010, 0b00000011, 0b00011001, 0b10000000,
         0b00001000, 0b10110010,
-    ]));
+    ];
+    const EXAMPLE: Fields = Fields(u64::from_le_bytes(EXAMPLE_RAW));
+
+    fn example() -> Name {
+        Name::new(
+            170254,
+            29,
+            1,
+            3,
+            128,
+            4,
+            2,
+            IndustryGroup::Construction,
+            true,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn example_decode() {
+        // see J1939-81 4.2.1.1
+        let name = Name::from_raw(EXAMPLE_RAW);
+
+        assert_eq!(name.identity(), 170254);
+        assert_eq!(name.manufacturer_code(), 29);
+        assert_eq!(name.ecu_instance(), 1);
+        assert_eq!(name.function_instance(), 3);
+        assert_eq!(name.function(), 128);
+        assert_eq!(name.vehicle_system(), 4);
+        assert_eq!(name.vehicle_system_instance(), 2);
+        assert_eq!(name.industry_group(), Some(IndustryGroup::Construction));
+        assert!(name.arbitrary_address_capable());
+        assert_eq!(name, example());
+    }
+
+    #[test]
+    fn example_encode() {
+        // see J1939-81 4.2.1.1
+        assert_eq!(example().as_raw(), EXAMPLE_RAW);
+    }
+
+    #[test]
+    fn fields_do_not_overlap() {
+        let name =
+            Name::new(0, 0, 0, 0, 0, 127, 0, IndustryGroup::Global, false)
+                .unwrap();
+        assert_eq!(name.vehicle_system_instance(), 0);
+        // bit 48 is reserved
+        assert_eq!(name.as_raw()[6], 0xFE);
+
+        let name =
+            Name::new(0, 0, 0, 0, 0, 0, 15, IndustryGroup::Global, false)
+                .unwrap();
+        assert_eq!(name.vehicle_system(), 0);
+        assert_eq!(name.as_raw()[7], 0x0F);
+    }
+
+    #[test]
+    fn ordering() {
+        let low =
+            Name::from_raw([0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x7F]);
+        let high = Name::from_raw([0, 0, 0, 0, 0, 0, 0, 0x80]);
+
+        // most significant byte is sent last
+        assert!(low < high);
+        assert!(Name::from_raw([0; 8]) < example());
+        assert_eq!(example().cmp(&example()), core::cmp::Ordering::Equal);
+    }
 
     #[test]
     fn example_identity() {
@@ -0,0 +1,592 @@
+//! Address claim procedure.
+
+use super::{AddressClaimed, CommandedAddress, CLAIM_TIMEOUT};
+use crate::frame::J1939Frame;
+use crate::identifier::Address;
+use crate::message::Request;
+use crate::name::Name;
+use crate::time::Instant;
+use core::ops::RangeInclusive;
+use core::time::Duration;
+use embedded_can::Frame;
+
+/// Addresses available to self-configurable controller applications.
+pub const ARBITRARY_ADDRESSES: RangeInclusive<u8> = 128..=247;
+
+/// Number of self-configurable addresses.
+const ARBITRARY_COUNT: usize =
+    (*ARBITRARY_ADDRESSES.end() - *ARBITRARY_ADDRESSES.start()) as usize + 1;
+
+/// Progress of the address claim.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ClaimState {
+    /// Claim sent, waiting for contenders.
+    Claiming,
+    /// Address claimed, normal traffic may be sent.
+    Claimed,
+    /// No address could be claimed.
+    CannotClaim,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Claim to be sent on the next poll.
+    Start,
+    /// Claim sent, waiting until the claim timeout.
+    Claiming {
+        until: Instant,
+    },
+    Claimed,
+    /// Lost the address, claim the next one or send cannot claim after a
+    /// pseudo-random delay.
+    Delay {
+        until: Instant,
+    },
+    /// Cannot claim sent, answer requests with another one after a
+    /// pseudo-random delay.
+    CannotClaim {
+        reply: Option<Instant>,
+    },
+}
+
+/// Claims and defends an address for a single controller application.
+///
+/// The claim is sent on the first `poll`. Received address claims are passed
+/// to `receive`, and `poll` returns the claims to send in response.
+#[derive(Debug, Clone)]
+pub struct AddressClaimer {
+    name: Name,
+    address: Address,
+    state: State,
+    /// Our claim needs to be sent again, to defend the address or answer a
+    /// request.
+    announce: bool,
+    /// Accepts commanded addresses.
+    commandable: bool,
+    /// Hash of the NAME holding each self-configurable address, zero when
+    /// free. A collision can only free an address that is still held, whose
+    /// owner then defends it.
+    owners: [u32; ARBITRARY_COUNT],
+    random: u32,
+}
+
+impl AddressClaimer {
+    /// Create a claimer for `name`, starting with `preferred_address`.
+    pub fn new(name: Name, preferred_address: Address) -> Self {
+        let bits = name.to_bits();
+        // xorshift needs a non-zero seed
+        let random = (bits as u32 ^ (bits >> 32) as u32) | 1;
+
+        Self {
+            name,
+            address: preferred_address,
+            state: State::Start,
+            announce: false,
+            commandable: false,
+            owners: [0; ARBITRARY_COUNT],
+            random,
+        }
+    }
+
+    /// Set whether the address can be changed with a [`CommandedAddress`].
+    pub fn with_commandable(mut self, commandable: bool) -> Self {
+        self.commandable = commandable;
+        self
+    }
+
+    /// NAME of the controller application.
+    pub fn name(&self) -> Name {
+        self.name
+    }
+
+    /// Progress of the address claim.
+    pub fn state(&self) -> ClaimState {
+        match self.state {
+            State::Start | State::Claiming { .. } | State::Delay { .. } => {
+                ClaimState::Claiming
+            }
+            State::Claimed => ClaimState::Claimed,
+            State::CannotClaim { .. } => ClaimState::CannotClaim,
+        }
+    }
+
+    /// Claimed address, once normal traffic may be sent.
+    pub fn address(&self) -> Option<Address> {
+        match self.state {
+            State::Claimed => Some(self.address),
+            _ => None,
+        }
+    }
+
+    /// Next address claim to send, if any.
+    pub fn poll(&mut self, now: Instant) -> Option<AddressClaimed> {
+        match self.state {
+            State::Start if !self.address.is_unicast() => {
+                self.lose(now);
+                None
+            }
+            State::Start => {
+                self.state = State::Claiming {
+                    until: now + CLAIM_TIMEOUT,
+                };
+                self.announce = false;
+                Some(AddressClaimed::new(self.name, self.address))
+            }
+            State::Claiming { until } if now >= until => {
+                self.state = State::Claimed;
+                self.poll(now)
+            }
+            State::Claiming { .. } | State::Claimed if self.announce => {
+                self.announce = false;
+                Some(AddressClaimed::new(self.name, self.address))
+            }
+            State::Delay { until } if now >= until => {
+                if self.address.is_unicast() {
+                    self.state = State::Start;
+                    self.poll(now)
+                } else {
+                    self.state = State::CannotClaim { reply: None };
+                    Some(AddressClaimed::cannot_claim(self.name))
+                }
+            }
+            State::CannotClaim { reply: Some(reply) } if now >= reply => {
+                self.state = State::CannotClaim { reply: None };
+                Some(AddressClaimed::cannot_claim(self.name))
+            }
+            _ => None,
+        }
+    }
+
+    /// Process a received frame.
+    ///
+    /// Frames other than address claims and requests for address claimed are
+    /// ignored.
+    pub fn receive<F: Frame>(
+        &mut self,
+        now: Instant,
+        frame: &J1939Frame<'_, F>,
+    ) {
+        if frame.pgn() == Request::PGN {
+            if let Ok(request) = Request::decode(frame.id(), frame.data()) {
+                self.request(now, &request);
+            }
+            return;
+        }
+
+        if frame.pgn() != AddressClaimed::PGN {
+            return;
+        }
+
+        let Ok(claim) = AddressClaimed::decode(frame.id(), frame.data()) else {
+            return;
+        };
+
+        if claim.name() == self.name {
+            return;
+        }
+
+        let address = claim.source_address();
+        let contested = address == self.address
+            && matches!(self.state, State::Claiming { .. } | State::Claimed);
+
+        if !contested {
+            self.track(claim.name(), address);
+        } else if self.name < claim.name() {
+            self.announce = true;
+        } else {
+            self.track(claim.name(), address);
+            self.lose(now);
+        }
+    }
+
+    /// Answer a request for address claimed.
+    fn request(&mut self, now: Instant, request: &Request) {
+        if request.pgn() != AddressClaimed::PGN {
+            return;
+        }
+
+        let destination_address = request.destination_address();
+
+        match self.state {
+            State::Claiming { .. } | State::Claimed
+                if request.is_global()
+                    || destination_address == self.address =>
+            {
+                self.announce = true;
+            }
+            State::CannotClaim { .. } if request.is_global() => {
+                self.state = State::CannotClaim {
+                    reply: Some(now + self.delay()),
+                };
+            }
+            _ => {}
+        }
+    }
+
+    /// Process a received commanded address.
+    ///
+    /// When the command is for this NAME the new address is claimed on the
+    /// next `poll`, even if an address was already claimed. Returns whether
+    /// the command was accepted.
+    pub fn command(&mut self, command: &CommandedAddress) -> bool {
+        if !self.commandable
+            || command.name() != self.name
+            || !command.new_address().is_unicast()
+        {
+            return false;
+        }
+
+        self.address = command.new_address();
+        self.announce = false;
+        self.state = State::Start;
+
+        true
+    }
+
+    /// Change the NAME, after adopting a pending NAME.
+    ///
+    /// The address is claimed again with the new NAME on the next `poll`,
+    /// starting with the current address.
+    pub fn set_name(&mut self, name: Name) {
+        self.name = name;
+        self.announce = false;
+        self.state = State::Start;
+    }
+
+    /// Give up the current address and pick another one if possible.
+    fn lose(&mut self, now: Instant) {
+        self.address = if self.name.arbitrary_address_capable() {
+            self.next_address().unwrap_or(Address::NULL)
+        } else {
+            Address::NULL
+        };
+        self.announce = false;
+        self.state = State::Delay {
+            until: now + self.delay(),
+        };
+    }
+
+    /// First free self-configurable address after the current one.
+    fn next_address(&self) -> Option<Address> {
+        let start = if ARBITRARY_ADDRESSES.contains(&self.address.as_raw()) {
+            self.address.as_raw() + 1
+        } else {
+            *ARBITRARY_ADDRESSES.start()
+        };
+
+        (start..=*ARBITRARY_ADDRESSES.end())
+            .chain(ARBITRARY_ADDRESSES.clone())
+            .find(|&address| !self.is_taken(address))
+            .map(Address::new)
+    }
+
+    fn is_taken(&self, address: u8) -> bool {
+        slot(address).is_some_and(|slot| self.owners[slot] != 0)
+    }
+
+    /// Record that `name` now holds `address`, releasing the address it held
+    /// before. Cannot claim is sent from the null address, which only
+    /// releases.
+    fn track(&mut self, name: Name, address: Address) {
+        // never zero, that marks a free address
+        let bits = name.to_bits().wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        let owner = (bits >> 32) as u32 | 1;
+
+        for slot in self.owners.iter_mut().filter(|slot| **slot == owner) {
+            *slot = 0;
+        }
+
+        if let Some(slot) = slot(address.as_raw()) {
+            self.owners[slot] = owner;
+        }
+    }
+
+    /// Pseudo-random delay of 0 to 153 ms.
+    fn delay(&mut self) -> Duration {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 17;
+        self.random ^= self.random << 5;
+
+        Duration::from_micros(600 * (self.random & 0xFF) as u64)
+    }
+}
+
+/// Index of a self-configurable address in the owner table.
+fn slot(address: u8) -> Option<usize> {
+    ARBITRARY_ADDRESSES
+        .contains(&address)
+        .then(|| (address - ARBITRARY_ADDRESSES.start()) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::MAX_DELAY;
+    use crate::testing::{at, name, TestFrame};
+
+    fn receive(
+        claimer: &mut AddressClaimer,
+        now: Instant,
+        claim: AddressClaimed,
+    ) {
+        let frame: TestFrame = claim.to_frame().unwrap();
+        claimer.receive(now, &J1939Frame::new(&frame).unwrap());
+    }
+
+    #[test]
+    fn claim() {
+        let mut claimer =
+            AddressClaimer::new(name(10, false), Address::new(0x25));
+
+        let claim = claimer.poll(at(0)).unwrap();
+        assert_eq!(claim.id().as_raw(), 0x18EE_FF25);
+        assert_eq!(claim.name(), claimer.name());
+        assert_eq!(claimer.state(), ClaimState::Claiming);
+        assert_eq!(claimer.address(), None);
+
+        assert_eq!(claimer.poll(at(249)), None);
+        assert_eq!(claimer.address(), None);
+        assert_eq!(claimer.poll(at(250)), None);
+        assert_eq!(claimer.state(), ClaimState::Claimed);
+        assert_eq!(claimer.address(), Some(Address::new(0x25)));
+
+        // our own claim echoed back is not a contender
+        receive(&mut claimer, at(300), claim);
+        assert_eq!(claimer.poll(at(300)), None);
+    }
+
+    #[test]
+    fn defend() {
+        let mut claimer =
+            AddressClaimer::new(name(10, false), Address::new(0x25));
+        claimer.poll(at(0)).unwrap();
+        claimer.poll(at(250));
+
+        // higher NAME loses
+        let contender =
+            AddressClaimed::new(name(11, false), Address::new(0x25));
+        receive(&mut claimer, at(300), contender);
+
+        let claim = claimer.poll(at(300)).unwrap();
+        assert_eq!(claim.source_address(), Address::new(0x25));
+        assert_eq!(claimer.address(), Some(Address::new(0x25)));
+        assert_eq!(claimer.poll(at(300)), None);
+    }
+
+    #[test]
+    fn cannot_claim() {
+        let mut claimer =
+            AddressClaimer::new(name(10, false), Address::new(0x25));
+        claimer.poll(at(0)).unwrap();
+
+        let contender = AddressClaimed::new(name(9, false), Address::new(0x25));
+        receive(&mut claimer, at(100), contender);
+        assert_eq!(claimer.address(), None);
+
+        let delay = at(100) + MAX_DELAY;
+        let claim = (100..=delay.as_millis())
+            .find_map(|millis| claimer.poll(at(millis)))
+            .unwrap();
+        assert!(claim.is_cannot_claim());
+        assert_eq!(claim.id().as_raw(), 0x18EE_FFFE);
+        assert_eq!(claimer.state(), ClaimState::CannotClaim);
+    }
+
+    fn request(
+        claimer: &mut AddressClaimer,
+        now: Instant,
+        destination_address: Address,
+    ) {
+        let request = Request::new(
+            AddressClaimed::PGN,
+            destination_address,
+            Address::new(0xF9),
+        );
+        let frame: TestFrame = request.to_frame().unwrap();
+        claimer.receive(now, &J1939Frame::new(&frame).unwrap());
+    }
+
+    #[test]
+    fn answer_request() {
+        let mut claimer =
+            AddressClaimer::new(name(10, false), Address::new(0x25));
+        claimer.poll(at(0)).unwrap();
+        claimer.poll(at(250));
+
+        request(&mut claimer, at(300), Address::GLOBAL);
+        let claim = claimer.poll(at(300)).unwrap();
+        assert_eq!(claim.id().as_raw(), 0x18EE_FF25);
+        assert_eq!(claimer.poll(at(300)), None);
+
+        request(&mut claimer, at(400), Address::new(0x25));
+        assert!(claimer.poll(at(400)).is_some());
+
+        // requests sent to other nodes are not ours to answer
+        request(&mut claimer, at(500), Address::new(0x26));
+        assert_eq!(claimer.poll(at(500)), None);
+    }
+
+    #[test]
+    fn answer_request_cannot_claim() {
+        let mut claimer =
+            AddressClaimer::new(name(10, false), Address::new(0x25));
+        claimer.poll(at(0)).unwrap();
+        let contender = AddressClaimed::new(name(9, false), Address::new(0x25));
+        receive(&mut claimer, at(100), contender);
+        let delay = at(100) + MAX_DELAY;
+        (100..=delay.as_millis())
+            .find_map(|millis| claimer.poll(at(millis)))
+            .unwrap();
+
+        // only global requests reach a node without an address
+        request(&mut claimer, at(1000), Address::new(0x25));
+        assert_eq!(claimer.poll(at(2000)), None);
+
+        request(&mut claimer, at(3000), Address::GLOBAL);
+        let delay = at(3000) + MAX_DELAY;
+        let claim = (3000..=delay.as_millis())
+            .find_map(|millis| claimer.poll(at(millis)))
+            .unwrap();
+        assert!(claim.is_cannot_claim());
+        assert_eq!(claimer.poll(at(4000)), None);
+        assert_eq!(claimer.state(), ClaimState::CannotClaim);
+    }
+
+    #[test]
+    fn arbitrary_address() {
+        let mut claimer =
+            AddressClaimer::new(name(10, true), Address::new(0x25));
+        claimer.poll(at(0)).unwrap();
+        claimer.poll(at(250));
+
+        // 128 is already in use
+        let other = AddressClaimed::new(name(1, true), Address::new(128));
+        receive(&mut claimer, at(260), other);
+
+        let contender = AddressClaimed::new(name(9, false), Address::new(0x25));
+        receive(&mut claimer, at(300), contender);
+        assert_eq!(claimer.address(), None);
+        assert_eq!(claimer.state(), ClaimState::Claiming);
+
+        let delay = at(300) + MAX_DELAY;
+        let claim = (300..=delay.as_millis())
+            .find_map(|millis| claimer.poll(at(millis)))
+            .unwrap();
+        assert_eq!(claim.source_address(), Address::new(129));
+
+        let claimed = at(delay.as_millis() + 250);
+        claimer.poll(claimed);
+        assert_eq!(claimer.address(), Some(Address::new(129)));
+    }
+
+    #[test]
+    fn freed_addresses() {
+        let mut claimer =
+            AddressClaimer::new(name(10, true), Address::new(0x25));
+        claimer.poll(at(0)).unwrap();
+        claimer.poll(at(250));
+
+        // every self-configurable address is claimed at some point
+        for address in ARBITRARY_ADDRESSES {
+            let other = name(100 + address as u32, true);
+            receive(
+                &mut claimer,
+                at(260),
+                AddressClaimed::new(other, address.into()),
+            );
+        }
+        // 128 moves away, 129 gives up
+        let moved = AddressClaimed::new(name(228, true), Address::new(0x30));
+        receive(&mut claimer, at(270), moved);
+        receive(
+            &mut claimer,
+            at(270),
+            AddressClaimed::cannot_claim(name(229, true)),
+        );
+
+        let contender = AddressClaimed::new(name(9, false), Address::new(0x25));
+        receive(&mut claimer, at(300), contender);
+
+        let delay = at(300) + MAX_DELAY;
+        let claim = (300..=delay.as_millis())
+            .find_map(|millis| claimer.poll(at(millis)))
+            .unwrap();
+        assert_eq!(claim.source_address(), Address::new(128));
+    }
+
+    #[test]
+    fn commanded() {
+        use crate::transport::bam::{BamReceiver, BamSender};
+
+        let mut claimer =
+            AddressClaimer::new(name(10, false), Address::new(0x25))
+                .with_commandable(true);
+        claimer.poll(at(0)).unwrap();
+        claimer.poll(at(250));
+
+        // service tool at 0xF9 broadcasts the command
+        let data =
+            CommandedAddress::new(claimer.name(), Address::new(0x30)).encode();
+        let mut sender =
+            BamSender::new(CommandedAddress::PGN, &data, Address::new(0xF9))
+                .unwrap();
+        let mut receiver = BamReceiver::<9>::new();
+
+        let mut command = None;
+        for millis in (0..200).step_by(50) {
+            if let Some(packet) = sender.poll(at(300 + millis)) {
+                let frame: TestFrame = packet.to_frame().unwrap();
+                let view = J1939Frame::new(&frame).unwrap();
+                if let Some(message) = receiver.receive(at(300), &view).unwrap()
+                {
+                    command = Some(
+                        CommandedAddress::from_reassembled(&message).unwrap(),
+                    );
+                }
+            }
+        }
+
+        let command = command.unwrap();
+        assert!(claimer.command(&command));
+        assert_eq!(claimer.address(), None);
+
+        let claim = claimer.poll(at(400)).unwrap();
+        assert_eq!(claim.source_address(), Address::new(0x30));
+        claimer.poll(at(650));
+        assert_eq!(claimer.address(), Some(Address::new(0x30)));
+
+        // commands for other NAMEs are ignored
+        let other = CommandedAddress::new(name(11, false), Address::new(0x31));
+        assert!(!claimer.command(&other));
+
+        let mut fixed =
+            AddressClaimer::new(name(10, false), Address::new(0x25));
+        assert!(!fixed.command(&command));
+    }
+
+    #[test]
+    fn set_name() {
+        let mut claimer =
+            AddressClaimer::new(name(10, false), Address::new(0x25));
+        claimer.poll(at(0)).unwrap();
+        claimer.poll(at(250));
+        assert_eq!(claimer.address(), Some(Address::new(0x25)));
+
+        claimer.set_name(name(12, false));
+        assert_eq!(claimer.name(), name(12, false));
+        assert_eq!(claimer.address(), None);
+
+        let claim = claimer.poll(at(300)).unwrap();
+        assert_eq!(claim.name(), name(12, false));
+        assert_eq!(claim.source_address(), Address::new(0x25));
+        claimer.poll(at(550));
+        assert_eq!(claimer.address(), Some(Address::new(0x25)));
+    }
+
+    #[test]
+    fn compact() {
+        // owners are tracked without a full address table
+        assert!(core::mem::size_of::<AddressClaimer>() <= 544);
+    }
+}
@@ -0,0 +1,700 @@
+//! NAME management.
+//!
+//! Lets a tool change the adjustable fields of a controller application's
+//! NAME, such as its ECU or function instance. Changes are first set as a
+//! pending NAME, which the controller application only starts using once
+//! told to adopt it.
+//!
+//! See SAEJ1939-81 4.2.5
+
+use crate::error::{IdError, MessageError, NameError};
+use crate::frame::J1939Frame;
+use crate::identifier::{Address, Extended, J1939Id, Pgn};
+use crate::name::{IndustryGroup, Name};
+use embedded_can::{ExtendedId, Frame};
+
+/// Masks of the adjustable fields in the upper 32 bits of the NAME, in
+/// qualifier bit order. Bit 3 of the qualifier is reserved.
+const FIELDS: [u32; 8] = [
+    0x0000_0007, // ECU instance
+    0x0000_00F8, // function instance
+    0x0000_FF00, // function
+    0,           // reserved
+    0x00FE_0000, // vehicle system
+    0x0F00_0000, // vehicle system instance
+    0x7000_0000, // industry group
+    0x8000_0000, // arbitrary address capable
+];
+
+/// What a NAME management message asks for or answers with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum NameMode {
+    /// Change fields of the pending NAME.
+    SetPending = 0,
+    /// Pending NAME, in response to a change or request.
+    PendingResponse = 1,
+    /// Ask for the pending NAME.
+    RequestPending = 2,
+    /// Ask for the current NAME.
+    RequestCurrent = 3,
+    /// Current NAME, in response to a request.
+    CurrentResponse = 4,
+    /// Start using the pending NAME.
+    AdoptPending = 5,
+    /// Command carried out.
+    Ack = 6,
+    /// Command refused.
+    Nack = 7,
+}
+
+impl TryFrom<u8> for NameMode {
+    type Error = MessageError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(NameMode::SetPending),
+            1 => Ok(NameMode::PendingResponse),
+            2 => Ok(NameMode::RequestPending),
+            3 => Ok(NameMode::RequestCurrent),
+            4 => Ok(NameMode::CurrentResponse),
+            5 => Ok(NameMode::AdoptPending),
+            6 => Ok(NameMode::Ack),
+            7 => Ok(NameMode::Nack),
+            _ => Err(MessageError::InvalidControlByte),
+        }
+    }
+}
+
+/// Why a NAME management command was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum NackReason {
+    /// Checksum does not match the current NAME.
+    Checksum = 0,
+    /// No pending NAME has been set.
+    NoPendingName = 1,
+    /// Changed fields don't make a valid NAME.
+    InvalidField = 2,
+    /// Pending NAME could not be stored.
+    StoreFailed = 3,
+}
+
+impl TryFrom<u8> for NackReason {
+    type Error = MessageError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(NackReason::Checksum),
+            1 => Ok(NackReason::NoPendingName),
+            2 => Ok(NackReason::InvalidField),
+            3 => Ok(NackReason::StoreFailed),
+            _ => Err(MessageError::InvalidControlByte),
+        }
+    }
+}
+
+/// NAME Management (PGN 37632).
+///
+/// | Byte | Content                                                    |
+/// |------|------------------------------------------------------------|
+/// | 1    | checksum of the current NAME                               |
+/// | 2    | qualifier, a cleared bit marks a field to change           |
+/// | 3    | mode in bits 4-1                                           |
+/// | 4    | NACK reason, reserved in other modes                       |
+/// | 5-8  | ECU instance to arbitrary address capable, as in the NAME  |
+///
+/// The identity number and manufacturer code can't be changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NameManagement {
+    checksum: u8,
+    qualifier: u8,
+    mode: NameMode,
+    reason: u8,
+    fields: u32,
+}
+
+impl NameManagement {
+    /// NAME management parameter group number.
+    pub const PGN: Pgn = Pgn::from_parts(false, false, 0x93, 0);
+
+    /// Payload length in bytes.
+    pub const LEN: usize = 8;
+
+    /// Default priority for NAME management.
+    pub const PRIORITY: u8 = 6;
+
+    fn new(current: Name, qualifier: u8, mode: NameMode, fields: Name) -> Self {
+        Self {
+            checksum: checksum(current),
+            qualifier,
+            mode,
+            reason: 0xFF,
+            fields: upper(fields),
+        }
+    }
+
+    /// Change every adjustable field in which `pending` differs from
+    /// `current`.
+    pub fn set_pending(current: Name, pending: Name) -> Self {
+        let changed = upper(current) ^ upper(pending);
+        let qualifier = FIELDS
+            .iter()
+            .enumerate()
+            .filter(|(_, &mask)| changed & mask != 0)
+            .fold(0xFF, |qualifier, (bit, _)| qualifier & !(1 << bit));
+
+        Self::new(current, qualifier, NameMode::SetPending, pending)
+    }
+
+    /// Ask the controller application with `current` NAME to adopt its
+    /// pending NAME.
+    pub fn adopt_pending(current: Name) -> Self {
+        Self::new(current, 0xFF, NameMode::AdoptPending, current)
+    }
+
+    /// Ask for the pending NAME.
+    pub fn request_pending() -> Self {
+        Self::request(NameMode::RequestPending)
+    }
+
+    /// Ask for the current NAME.
+    pub fn request_current() -> Self {
+        Self::request(NameMode::RequestCurrent)
+    }
+
+    fn request(mode: NameMode) -> Self {
+        Self {
+            checksum: 0xFF,
+            qualifier: 0xFF,
+            mode,
+            reason: 0xFF,
+            fields: u32::MAX,
+        }
+    }
+
+    /// Answer with the pending NAME.
+    pub fn pending_response(current: Name, pending: Name) -> Self {
+        Self::new(current, 0xFF, NameMode::PendingResponse, pending)
+    }
+
+    /// Answer with the current NAME.
+    pub fn current_response(current: Name) -> Self {
+        Self::new(current, 0xFF, NameMode::CurrentResponse, current)
+    }
+
+    /// Acknowledge a command.
+    pub fn ack(current: Name) -> Self {
+        Self::new(current, 0xFF, NameMode::Ack, current)
+    }
+
+    /// Refuse a command.
+    pub fn nack(current: Name, reason: NackReason) -> Self {
+        Self {
+            reason: reason as u8,
+            ..Self::new(current, 0xFF, NameMode::Nack, current)
+        }
+    }
+
+    /// Decode a NAME management message from its identifier and payload.
+    pub fn decode(id: ExtendedId, data: &[u8]) -> Result<Self, MessageError> {
+        if id.pgn() != Self::PGN {
+            return Err(MessageError::UnexpectedPgn);
+        }
+
+        let data: [u8; Self::LEN] =
+            data.try_into().map_err(|_| MessageError::InvalidLength)?;
+
+        Ok(Self {
+            checksum: data[0],
+            qualifier: data[1],
+            mode: NameMode::try_from(data[2] & 0x0F)?,
+            reason: data[3],
+            fields: u32::from_le_bytes([data[4], data[5], data[6], data[7]]),
+        })
+    }
+
+    /// Decode a NAME management message from a received frame.
+    pub fn from_frame<F: Frame>(frame: &F) -> Result<Self, MessageError> {
+        let frame = J1939Frame::new(frame)?;
+
+        Self::decode(frame.id(), frame.data())
+    }
+
+    /// Encode the NAME management payload.
+    pub fn encode(&self) -> [u8; 8] {
+        let fields = self.fields.to_le_bytes();
+
+        [
+            self.checksum,
+            self.qualifier,
+            0xF0 | self.mode as u8,
+            self.reason,
+            fields[0],
+            fields[1],
+            fields[2],
+            fields[3],
+        ]
+    }
+
+    /// Identifier to send the message with.
+    ///
+    /// Fails if `priority` is larger than 7.
+    pub fn id(
+        &self,
+        priority: u8,
+        destination_address: Address,
+        source_address: Address,
+    ) -> Result<J1939Id, IdError> {
+        J1939Id::new(priority, Self::PGN, destination_address, source_address)
+    }
+
+    /// Create a frame carrying the message with the default priority.
+    pub fn to_frame<F: Frame>(
+        &self,
+        destination_address: Address,
+        source_address: Address,
+    ) -> Option<F> {
+        let id = self
+            .id(Self::PRIORITY, destination_address, source_address)
+            .ok()?;
+
+        F::new(id, &self.encode())
+    }
+
+    /// Checksum of the current NAME of the controller application.
+    pub fn checksum(&self) -> u8 {
+        self.checksum
+    }
+
+    /// What the message asks for or answers with.
+    pub fn mode(&self) -> NameMode {
+        self.mode
+    }
+
+    /// Why the command was refused, for a NACK.
+    pub fn nack_reason(&self) -> Option<NackReason> {
+        match self.mode {
+            NameMode::Nack => NackReason::try_from(self.reason).ok(),
+            _ => None,
+        }
+    }
+
+    /// Whether the message is for the controller application with `name`.
+    pub fn is_for(&self, name: Name) -> bool {
+        self.checksum == checksum(name)
+    }
+
+    /// NAME carried by a response, completing the fields that can't change
+    /// from `name`.
+    pub fn name(&self, name: Name) -> Name {
+        let lower = name.to_bits() & 0xFFFF_FFFF;
+
+        Name::from_raw(((self.fields as u64) << 32 | lower).to_le_bytes())
+    }
+
+    /// Apply the fields marked for change to `name`.
+    ///
+    /// Fails if the result is not a valid NAME, as checked by [`Name::new`].
+    pub fn apply(&self, name: Name) -> Result<Name, NameError> {
+        let mask = FIELDS
+            .iter()
+            .enumerate()
+            .filter(|(bit, _)| self.qualifier & (1 << bit) == 0)
+            .fold(0, |mask, (_, field)| mask | field);
+
+        let upper = (upper(name) & !mask) | (self.fields & mask);
+        let lower = name.to_bits() & 0xFFFF_FFFF;
+        let name = Name::from_raw(((upper as u64) << 32 | lower).to_le_bytes());
+
+        // industry group is the only field that can be out of range
+        let industry_group =
+            IndustryGroup::try_from((upper >> 28) as u8 & 0x7)?;
+
+        Name::new(
+            name.identity(),
+            name.manufacturer_code(),
+            name.ecu_instance(),
+            name.function_instance(),
+            name.function(),
+            name.vehicle_system(),
+            name.vehicle_system_instance(),
+            industry_group,
+            name.arbitrary_address_capable(),
+        )
+    }
+}
+
+/// Sum of the bytes of `name`.
+fn checksum(name: Name) -> u8 {
+    name.as_raw()
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+/// Upper 32 bits of `name`, holding the adjustable fields.
+fn upper(name: Name) -> u32 {
+    (name.to_bits() >> 32) as u32
+}
+
+/// Non-volatile storage for the NAME of a controller application.
+pub trait NameStore {
+    /// Persist `name` so it is used from the next start on.
+    ///
+    /// Returns `false` if it could not be stored, in which case the NAME is
+    /// not adopted.
+    fn store(&mut self, name: Name) -> bool;
+}
+
+impl<T: NameStore> NameStore for &mut T {
+    fn store(&mut self, name: Name) -> bool {
+        T::store(self, name)
+    }
+}
+
+/// Outcome of a NAME management message handled by a [`NameServer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameEvent {
+    /// Send the response back to the source of the message.
+    Respond(NameManagement),
+    /// The pending NAME was adopted. Send the response back, then claim an
+    /// address with the new NAME, see
+    /// [`AddressClaimer::set_name`](super::claim::AddressClaimer::set_name).
+    Adopted {
+        response: NameManagement,
+        name: Name,
+    },
+}
+
+impl NameEvent {
+    /// Response to send back to the source of the message.
+    pub fn response(&self) -> NameManagement {
+        match self {
+            NameEvent::Respond(response) => *response,
+            NameEvent::Adopted { response, .. } => *response,
+        }
+    }
+}
+
+/// Answers NAME management messages for a controller application.
+#[derive(Debug, Clone)]
+pub struct NameServer<S> {
+    current: Name,
+    pending: Option<Name>,
+    store: S,
+}
+
+impl<S: NameStore> NameServer<S> {
+    /// Create a server for a controller application using `current`.
+    pub fn new(current: Name, store: S) -> Self {
+        Self {
+            current,
+            pending: None,
+            store,
+        }
+    }
+
+    /// NAME in use.
+    pub fn current(&self) -> Name {
+        self.current
+    }
+
+    /// NAME to be adopted, if one was set.
+    pub fn pending(&self) -> Option<Name> {
+        self.pending
+    }
+
+    /// Storage the NAME is persisted to.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Process a received frame.
+    ///
+    /// Frames other than NAME management are ignored. Commands sent to the
+    /// global address are only answered when their checksum matches the
+    /// current NAME, commands sent to us are refused otherwise.
+    pub fn receive<F: Frame>(
+        &mut self,
+        frame: &J1939Frame<'_, F>,
+    ) -> Option<NameEvent> {
+        if frame.pgn() != NameManagement::PGN {
+            return None;
+        }
+
+        let message = NameManagement::decode(frame.id(), frame.data()).ok()?;
+        let current = self.current;
+        let nack = |reason| NameManagement::nack(current, reason);
+
+        let response = match message.mode() {
+            NameMode::SetPending | NameMode::AdoptPending
+                if !message.is_for(current) =>
+            {
+                if frame.destination_address().is_global() {
+                    // meant for another NAME
+                    return None;
+                }
+                nack(NackReason::Checksum)
+            }
+            NameMode::SetPending => {
+                let base = self.pending.unwrap_or(current);
+                match message.apply(base) {
+                    Ok(pending) => {
+                        self.pending = Some(pending);
+                        NameManagement::pending_response(current, pending)
+                    }
+                    Err(_) => nack(NackReason::InvalidField),
+                }
+            }
+            NameMode::RequestPending => match self.pending {
+                Some(pending) => {
+                    NameManagement::pending_response(current, pending)
+                }
+                None => nack(NackReason::NoPendingName),
+            },
+            NameMode::RequestCurrent => {
+                NameManagement::current_response(current)
+            }
+            NameMode::AdoptPending => {
+                let Some(pending) = self.pending else {
+                    return Some(NameEvent::Respond(nack(
+                        NackReason::NoPendingName,
+                    )));
+                };
+                if !self.store.store(pending) {
+                    return Some(NameEvent::Respond(nack(
+                        NackReason::StoreFailed,
+                    )));
+                }
+
+                self.current = pending;
+                self.pending = None;
+
+                return Some(NameEvent::Adopted {
+                    response: NameManagement::ack(current),
+                    name: pending,
+                });
+            }
+            NameMode::PendingResponse
+            | NameMode::CurrentResponse
+            | NameMode::Ack
+            | NameMode::Nack => return None,
+        };
+
+        Some(NameEvent::Respond(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{instance_name, TestFrame};
+
+    #[derive(Default)]
+    struct Flash {
+        name: Option<Name>,
+        broken: bool,
+    }
+
+    impl NameStore for Flash {
+        fn store(&mut self, name: Name) -> bool {
+            self.name = Some(name);
+            !self.broken
+        }
+    }
+
+    #[test]
+    fn codec() {
+        let message = NameManagement::set_pending(
+            instance_name(1, 3),
+            instance_name(2, 3),
+        );
+        assert_eq!(message.mode(), NameMode::SetPending);
+        assert!(message.is_for(instance_name(1, 3)));
+
+        let data = message.encode();
+        // only the ECU instance is marked for change
+        assert_eq!(data[1], 0xFE);
+        assert_eq!(data[2], 0xF0);
+        assert_eq!(&data[4..], &instance_name(2, 3).as_raw()[4..]);
+
+        let frame: TestFrame = message
+            .to_frame(Address::new(0x25), Address::new(0xF9))
+            .unwrap();
+        let frame = J1939Frame::new(&frame).unwrap();
+        assert_eq!(frame.id().as_raw(), 0x1893_25F9);
+        assert_eq!(
+            NameManagement::decode(frame.id(), frame.data()),
+            Ok(message)
+        );
+
+        let mut data = data;
+        data[2] = 0xF8;
+        assert_eq!(
+            NameManagement::decode(frame.id(), &data),
+            Err(MessageError::InvalidControlByte)
+        );
+        assert_eq!(
+            NameManagement::decode(frame.id(), &data[..7]),
+            Err(MessageError::InvalidLength)
+        );
+        assert_eq!(NameManagement::PGN.as_raw(), 37632);
+    }
+
+    #[test]
+    fn apply() {
+        let message = NameManagement::set_pending(
+            instance_name(1, 3),
+            instance_name(2, 7),
+        );
+        assert_eq!(message.apply(instance_name(1, 3)), Ok(instance_name(2, 7)));
+        // unmarked fields are kept
+        assert_eq!(message.apply(instance_name(0, 0)), Ok(instance_name(2, 7)));
+
+        let mut data = message.encode();
+        data[1] = 0xBF;
+        data[7] |= 0x70;
+        let id = J1939Id::new(6, NameManagement::PGN, 0.into(), 0.into())
+            .unwrap()
+            .into();
+        let reserved = NameManagement::decode(id, &data).unwrap();
+        assert_eq!(
+            reserved.apply(instance_name(1, 3)),
+            Err(NameError::IndustryGroup)
+        );
+    }
+
+    fn receive<S: NameStore>(
+        server: &mut NameServer<S>,
+        destination: u8,
+        message: NameManagement,
+    ) -> Option<NameEvent> {
+        let frame: TestFrame = message
+            .to_frame(Address::new(destination), Address::new(0xF9))
+            .unwrap();
+        server.receive(&J1939Frame::new(&frame).unwrap())
+    }
+
+    #[test]
+    fn server() {
+        let mut flash = Flash::default();
+        let mut server = NameServer::new(instance_name(1, 3), &mut flash);
+
+        let response =
+            receive(&mut server, 0x25, NameManagement::request_pending())
+                .unwrap()
+                .response();
+        assert_eq!(response.mode(), NameMode::Nack);
+        assert_eq!(response.nack_reason(), Some(NackReason::NoPendingName));
+
+        // checksum of another NAME
+        let other = NameManagement::set_pending(
+            instance_name(0, 0),
+            instance_name(2, 3),
+        );
+        assert_eq!(receive(&mut server, 0xFF, other), None);
+        let response = receive(&mut server, 0x25, other).unwrap().response();
+        assert_eq!(response.nack_reason(), Some(NackReason::Checksum));
+
+        let set = NameManagement::set_pending(
+            instance_name(1, 3),
+            instance_name(1, 5),
+        );
+        let response = receive(&mut server, 0x25, set).unwrap().response();
+        assert_eq!(response.mode(), NameMode::PendingResponse);
+        assert_eq!(response.nack_reason(), None);
+        assert_eq!(response.name(instance_name(1, 3)), instance_name(1, 5));
+
+        let set = NameManagement::set_pending(
+            instance_name(1, 3),
+            instance_name(2, 3),
+        );
+        receive(&mut server, 0xFF, set).unwrap();
+        assert_eq!(server.pending(), Some(instance_name(2, 5)));
+        assert_eq!(server.current(), instance_name(1, 3));
+
+        let response =
+            receive(&mut server, 0x25, NameManagement::request_current())
+                .unwrap()
+                .response();
+        assert_eq!(response.mode(), NameMode::CurrentResponse);
+        assert_eq!(response.name(instance_name(0, 0)), instance_name(1, 3));
+
+        let event = receive(
+            &mut server,
+            0x25,
+            NameManagement::adopt_pending(instance_name(1, 3)),
+        )
+        .unwrap();
+        assert_eq!(
+            event,
+            NameEvent::Adopted {
+                response: NameManagement::ack(instance_name(1, 3)),
+                name: instance_name(2, 5),
+            }
+        );
+        assert_eq!(server.current(), instance_name(2, 5));
+        assert_eq!(server.pending(), None);
+        assert_eq!(flash.name, Some(instance_name(2, 5)));
+    }
+
+    #[test]
+    fn nack_reason() {
+        let message =
+            NameManagement::nack(instance_name(1, 3), NackReason::InvalidField);
+        let data = message.encode();
+        assert_eq!(data[3], 2);
+
+        let id = J1939Id::new(6, NameManagement::PGN, 0.into(), 0.into())
+            .unwrap()
+            .into();
+        let decoded = NameManagement::decode(id, &data).unwrap();
+        assert_eq!(decoded.nack_reason(), Some(NackReason::InvalidField));
+
+        let mut flash = Flash::default();
+        let mut server = NameServer::new(instance_name(1, 3), &mut flash);
+        let mut data = NameManagement::set_pending(
+            instance_name(1, 3),
+            instance_name(1, 4),
+        )
+        .encode();
+        // industry group marked for change, but reserved bit set
+        data[1] = 0xBF;
+        data[7] |= 0x70;
+        let frame = crate::testing::frame(0x1893_25F9, &data);
+        let response = server
+            .receive(&J1939Frame::new(&frame).unwrap())
+            .unwrap()
+            .response();
+        assert_eq!(response.nack_reason(), Some(NackReason::InvalidField));
+    }
+
+    #[test]
+    fn store_fails() {
+        let flash = Flash {
+            broken: true,
+            ..Default::default()
+        };
+        let mut server = NameServer::new(instance_name(1, 3), flash);
+
+        let set = NameManagement::set_pending(
+            instance_name(1, 3),
+            instance_name(1, 4),
+        );
+        receive(&mut server, 0x25, set).unwrap();
+
+        let response = receive(
+            &mut server,
+            0x25,
+            NameManagement::adopt_pending(instance_name(1, 3)),
+        )
+        .unwrap()
+        .response();
+        assert_eq!(response.mode(), NameMode::Nack);
+        assert_eq!(response.nack_reason(), Some(NackReason::StoreFailed));
+        assert_eq!(server.current(), instance_name(1, 3));
+        assert_eq!(server.pending(), Some(instance_name(1, 4)));
+    }
+}
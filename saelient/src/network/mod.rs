@@ -0,0 +1,260 @@
+//! Network management.
+//!
+//! Each controller application on the network is identified by its
+//! [`Name`], and has to claim a source address before it can send anything
+//! else. Claims are settled by comparing NAMEs, the lowest value wins.
+//!
+//! See SAEJ1939-81
+
+pub mod claim;
+pub mod management;
+pub mod scan;
+pub mod send;
+pub mod table;
+
+use crate::error::MessageError;
+use crate::frame::J1939Frame;
+use crate::identifier::{Address, Extended, J1939Id, Pgn, SourceAddress};
+use crate::name::Name;
+use crate::transport::Reassembled;
+use core::time::Duration;
+use embedded_can::{ExtendedId, Frame};
+
+/// Time to wait after claiming an address before sending other messages.
+pub const CLAIM_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Longest pseudo-random delay before sending a claim or cannot claim after
+/// losing an address.
+pub const MAX_DELAY: Duration = Duration::from_micros(600 * 255);
+
+/// Time to wait for address claims after a request for address claimed.
+pub const SCAN_TIMEOUT: Duration = Duration::from_millis(1250);
+
+/// Address Claimed (PGN 60928).
+///
+/// Sent with [`Address::NULL`] as the source address it is a Cannot Claim
+/// Address message instead.
+///
+/// See SAEJ1939-81 4.2.2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddressClaimed {
+    name: Name,
+    source_address: Address,
+}
+
+impl AddressClaimed {
+    /// Address claimed parameter group number.
+    pub const PGN: Pgn = Pgn::from_parts(false, false, 0xEE, 0);
+
+    /// Payload length in bytes.
+    pub const LEN: usize = 8;
+
+    /// Default priority for address claims.
+    pub const PRIORITY: u8 = 6;
+
+    /// Claim `source_address` for `name`.
+    pub fn new(name: Name, source_address: Address) -> Self {
+        Self {
+            name,
+            source_address,
+        }
+    }
+
+    /// Announce that `name` could not claim an address.
+    pub fn cannot_claim(name: Name) -> Self {
+        Self::new(name, Address::NULL)
+    }
+
+    /// Decode an address claim from its identifier and payload.
+    pub fn decode(id: ExtendedId, data: &[u8]) -> Result<Self, MessageError> {
+        if id.pgn() != Self::PGN {
+            return Err(MessageError::UnexpectedPgn);
+        }
+
+        let data: [u8; Self::LEN] =
+            data.try_into().map_err(|_| MessageError::InvalidLength)?;
+
+        Ok(Self {
+            name: Name::from_raw(data),
+            source_address: id.source_address(),
+        })
+    }
+
+    /// Decode an address claim from a received frame.
+    pub fn from_frame<F: Frame>(frame: &F) -> Result<Self, MessageError> {
+        let frame = J1939Frame::new(frame)?;
+
+        Self::decode(frame.id(), frame.data())
+    }
+
+    /// Encode the address claim payload.
+    pub fn encode(&self) -> [u8; 8] {
+        self.name.as_raw()
+    }
+
+    /// Identifier to send the address claim with.
+    pub fn id(&self) -> J1939Id {
+        // address claimed is PDU1 and default priority is in range
+        J1939Id::new(
+            Self::PRIORITY,
+            Self::PGN,
+            Address::GLOBAL,
+            self.source_address,
+        )
+        .unwrap()
+    }
+
+    /// Create a frame carrying the address claim.
+    pub fn to_frame<F: Frame>(&self) -> Option<F> {
+        F::new(self.id(), &self.encode())
+    }
+
+    /// NAME of the controller application.
+    pub fn name(&self) -> Name {
+        self.name
+    }
+
+    /// Address being claimed.
+    pub fn source_address(&self) -> Address {
+        self.source_address
+    }
+
+    /// The controller application could not claim an address.
+    pub fn is_cannot_claim(&self) -> bool {
+        self.source_address.is_null()
+    }
+}
+
+/// Commanded Address (PGN 65240).
+///
+/// Tells the controller application with a matching NAME to claim a new
+/// address. The message is 9 bytes long, so it is sent with the transport
+/// protocol, normally as a broadcast.
+///
+/// See SAEJ1939-81 4.2.4
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandedAddress {
+    name: Name,
+    new_address: Address,
+}
+
+impl CommandedAddress {
+    /// Commanded address parameter group number.
+    pub const PGN: Pgn = Pgn::from_parts(false, false, 0xFE, 0xD8);
+
+    /// Payload length in bytes.
+    pub const LEN: usize = 9;
+
+    /// Command the controller application with `name` to use `new_address`.
+    pub fn new(name: Name, new_address: Address) -> Self {
+        Self { name, new_address }
+    }
+
+    /// Decode a commanded address payload.
+    pub fn decode(data: &[u8]) -> Result<Self, MessageError> {
+        if data.len() != Self::LEN {
+            return Err(MessageError::InvalidLength);
+        }
+
+        // length checked above
+        let name = data[..8].try_into().unwrap();
+
+        Ok(Self {
+            name: Name::from_raw(name),
+            new_address: Address::new(data[8]),
+        })
+    }
+
+    /// Decode a commanded address from a message received with the
+    /// transport protocol.
+    pub fn from_reassembled(
+        message: &Reassembled<'_>,
+    ) -> Result<Self, MessageError> {
+        if message.pgn() != Self::PGN {
+            return Err(MessageError::UnexpectedPgn);
+        }
+
+        Self::decode(message.data())
+    }
+
+    /// Encode the commanded address payload.
+    pub fn encode(&self) -> [u8; 9] {
+        let name = self.name.as_raw();
+
+        [
+            name[0],
+            name[1],
+            name[2],
+            name[3],
+            name[4],
+            name[5],
+            name[6],
+            name[7],
+            self.new_address.as_raw(),
+        ]
+    }
+
+    /// NAME of the controller application being commanded.
+    pub fn name(&self) -> Name {
+        self.name
+    }
+
+    /// Address the controller application should claim.
+    pub fn new_address(&self) -> Address {
+        self.new_address
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestFrame;
+
+    // example taken from J1939-81 4.2.1.1
+    const EXAMPLE: [u8; 8] = [
+        0b00001110, 0b10011001, 0b10100010, 0b00000011, 0b00011001, 0b10000000,
+        0b00001000, 0b10110010,
+    ];
+
+    #[test]
+    fn address_claimed() {
+        let frame = crate::testing::frame(0x18EE_FF25, &EXAMPLE);
+        let claim = AddressClaimed::from_frame(&frame).unwrap();
+
+        assert_eq!(claim.source_address(), Address::new(0x25));
+        assert_eq!(claim.name().identity(), 170254);
+        assert!(!claim.is_cannot_claim());
+        assert_eq!(claim.encode(), EXAMPLE);
+
+        let frame: TestFrame = claim.to_frame().unwrap();
+        assert_eq!(AddressClaimed::from_frame(&frame), Ok(claim));
+
+        let cannot = AddressClaimed::cannot_claim(claim.name());
+        assert_eq!(cannot.id().as_raw(), 0x18EE_FFFE);
+        assert!(cannot.is_cannot_claim());
+
+        let frame = crate::testing::frame(0x18EE_FF25, &EXAMPLE[..7]);
+        assert_eq!(
+            AddressClaimed::from_frame(&frame),
+            Err(MessageError::InvalidLength)
+        );
+    }
+
+    #[test]
+    fn commanded_address() {
+        let mut data = [0; 9];
+        data[..8].copy_from_slice(&EXAMPLE);
+        data[8] = 0x42;
+
+        let command = CommandedAddress::decode(&data).unwrap();
+        assert_eq!(command.name(), Name::from_raw(EXAMPLE));
+        assert_eq!(command.new_address(), Address::new(0x42));
+        assert_eq!(command.encode(), data);
+
+        assert_eq!(
+            CommandedAddress::decode(&data[..8]),
+            Err(MessageError::InvalidLength)
+        );
+        assert_eq!(CommandedAddress::PGN.as_raw(), 65240);
+    }
+}
@@ -0,0 +1,156 @@
+//! Listing the controller applications on the network.
+
+use super::{AddressClaimed, SCAN_TIMEOUT};
+use crate::frame::J1939Frame;
+use crate::identifier::Address;
+use crate::message::Request;
+use crate::name::Name;
+use crate::time::Instant;
+use embedded_can::Frame;
+
+/// Collects the address claims sent in response to a global request for
+/// address claimed.
+///
+/// Up to `N` controller applications are recorded, including those that
+/// answer with cannot claim.
+#[derive(Debug, Clone)]
+pub struct AddressScan<const N: usize> {
+    claims: [Option<AddressClaimed>; N],
+    len: usize,
+    until: Instant,
+    overflow: bool,
+}
+
+impl<const N: usize> AddressScan<N> {
+    /// Start a scan.
+    ///
+    /// Returns the scan and the request to send. Nodes without an address
+    /// can scan from [`Address::NULL`].
+    pub fn start(now: Instant, source_address: Address) -> (Self, Request) {
+        let scan = Self {
+            claims: [None; N],
+            len: 0,
+            until: now + SCAN_TIMEOUT,
+            overflow: false,
+        };
+        let request =
+            Request::new(AddressClaimed::PGN, Address::GLOBAL, source_address);
+
+        (scan, request)
+    }
+
+    /// Whether the time for answers has passed.
+    pub fn is_done(&self, now: Instant) -> bool {
+        now >= self.until
+    }
+
+    /// More controller applications answered than could be recorded.
+    pub fn overflowed(&self) -> bool {
+        self.overflow
+    }
+
+    /// Claims received so far, one for each NAME.
+    pub fn claims(&self) -> impl Iterator<Item = &AddressClaimed> {
+        self.claims[..self.len].iter().flatten()
+    }
+
+    /// Address claimed by `name`, if it answered.
+    pub fn address_of(&self, name: Name) -> Option<Address> {
+        self.claims()
+            .find(|claim| claim.name() == name)
+            .map(AddressClaimed::source_address)
+    }
+
+    /// Process a received frame.
+    ///
+    /// Frames other than address claims, and claims arriving after the scan
+    /// is done, are ignored.
+    pub fn receive<F: Frame>(
+        &mut self,
+        now: Instant,
+        frame: &J1939Frame<'_, F>,
+    ) {
+        if self.is_done(now) || frame.pgn() != AddressClaimed::PGN {
+            return;
+        }
+
+        let Ok(claim) = AddressClaimed::decode(frame.id(), frame.data()) else {
+            return;
+        };
+
+        // a later claim by the same NAME replaces the earlier one
+        let existing = self.claims[..self.len]
+            .iter_mut()
+            .flatten()
+            .find(|existing| existing.name() == claim.name());
+
+        if let Some(existing) = existing {
+            *existing = claim;
+        } else if self.len < N {
+            self.claims[self.len] = Some(claim);
+            self.len += 1;
+        } else {
+            self.overflow = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{at, name, TestFrame};
+
+    fn receive<const N: usize>(
+        scan: &mut AddressScan<N>,
+        millis: u64,
+        claim: AddressClaimed,
+    ) {
+        let frame: TestFrame = claim.to_frame().unwrap();
+        scan.receive(at(millis), &J1939Frame::new(&frame).unwrap());
+    }
+
+    #[test]
+    fn scan() {
+        let (mut scan, request) =
+            AddressScan::<2>::start(at(1000), Address::NULL);
+        assert_eq!(request.id().as_raw(), 0x18EA_FFFE);
+        assert_eq!(request.pgn(), AddressClaimed::PGN);
+
+        receive(
+            &mut scan,
+            1010,
+            AddressClaimed::new(name(1, false), 0x10.into()),
+        );
+        receive(
+            &mut scan,
+            1100,
+            AddressClaimed::cannot_claim(name(2, false)),
+        );
+        receive(
+            &mut scan,
+            1200,
+            AddressClaimed::new(name(1, false), 0x11.into()),
+        );
+        assert!(!scan.overflowed());
+
+        receive(
+            &mut scan,
+            1300,
+            AddressClaimed::new(name(3, false), 0x12.into()),
+        );
+        assert!(scan.overflowed());
+
+        // too late
+        assert!(scan.is_done(at(2250)));
+        receive(
+            &mut scan,
+            2250,
+            AddressClaimed::new(name(2, false), 0x13.into()),
+        );
+
+        assert_eq!(scan.claims().count(), 2);
+        assert_eq!(scan.address_of(name(1, false)), Some(Address::new(0x11)));
+        assert_eq!(scan.address_of(name(2, false)), Some(Address::NULL));
+        assert_eq!(scan.address_of(name(3, false)), None);
+    }
+}
@@ -0,0 +1,533 @@
+//! Sending to controller applications by NAME.
+//!
+//! Source addresses can change whenever a controller application loses an
+//! address claim or is commanded to a new address. Messages addressed to a
+//! [`Destination`] are resolved through the [`AddressTable`] right before
+//! they are sent.
+
+use super::table::AddressTable;
+use crate::error::NetworkError;
+use crate::identifier::{Address, J1939Id, Pgn};
+use crate::name::{IndustryGroup, Name};
+use crate::time::Instant;
+use core::time::Duration;
+use embedded_can::Frame;
+
+/// Set of NAME fields a controller application has to match.
+///
+/// Fields that are not set match anything, so the default pattern matches
+/// every NAME.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NamePattern {
+    manufacturer_code: Option<u16>,
+    ecu_instance: Option<u8>,
+    function_instance: Option<u8>,
+    function: Option<u8>,
+    vehicle_system: Option<u8>,
+    vehicle_system_instance: Option<u8>,
+    industry_group: Option<IndustryGroup>,
+}
+
+impl NamePattern {
+    /// Create a pattern matching every NAME.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Match the manufacturer code.
+    pub fn with_manufacturer_code(mut self, manufacturer_code: u16) -> Self {
+        self.manufacturer_code = Some(manufacturer_code);
+        self
+    }
+
+    /// Match the ECU instance.
+    pub fn with_ecu_instance(mut self, ecu_instance: u8) -> Self {
+        self.ecu_instance = Some(ecu_instance);
+        self
+    }
+
+    /// Match the function instance.
+    pub fn with_function_instance(mut self, function_instance: u8) -> Self {
+        self.function_instance = Some(function_instance);
+        self
+    }
+
+    /// Match the function.
+    pub fn with_function(mut self, function: u8) -> Self {
+        self.function = Some(function);
+        self
+    }
+
+    /// Match the vehicle system.
+    pub fn with_vehicle_system(mut self, vehicle_system: u8) -> Self {
+        self.vehicle_system = Some(vehicle_system);
+        self
+    }
+
+    /// Match the vehicle system instance.
+    pub fn with_vehicle_system_instance(
+        mut self,
+        vehicle_system_instance: u8,
+    ) -> Self {
+        self.vehicle_system_instance = Some(vehicle_system_instance);
+        self
+    }
+
+    /// Match the industry group.
+    pub fn with_industry_group(
+        mut self,
+        industry_group: IndustryGroup,
+    ) -> Self {
+        self.industry_group = Some(industry_group);
+        self
+    }
+
+    /// Whether `name` has all the fields of the pattern.
+    pub fn matches(&self, name: Name) -> bool {
+        fn field<T: PartialEq>(pattern: Option<T>, value: T) -> bool {
+            pattern.is_none_or(|pattern| pattern == value)
+        }
+
+        field(self.manufacturer_code, name.manufacturer_code())
+            && field(self.ecu_instance, name.ecu_instance())
+            && field(self.function_instance, name.function_instance())
+            && field(self.function, name.function())
+            && field(self.vehicle_system, name.vehicle_system())
+            && field(
+                self.vehicle_system_instance,
+                name.vehicle_system_instance(),
+            )
+            && (self.industry_group.is_none()
+                || self.industry_group == name.industry_group())
+    }
+}
+
+/// Controller application a message is sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Destination {
+    /// Exactly this NAME.
+    Name(Name),
+    /// The lowest address whose NAME matches the pattern.
+    Pattern(NamePattern),
+}
+
+impl Destination {
+    /// Whether `name` is this destination.
+    pub fn matches(&self, name: Name) -> bool {
+        match self {
+            Destination::Name(destination) => *destination == name,
+            Destination::Pattern(pattern) => pattern.matches(name),
+        }
+    }
+
+    /// Current address of the destination in `table`.
+    pub fn resolve(&self, table: &AddressTable) -> Option<Address> {
+        table.find(|name| self.matches(name))
+    }
+}
+
+impl From<Name> for Destination {
+    fn from(name: Name) -> Self {
+        Destination::Name(name)
+    }
+}
+
+impl From<NamePattern> for Destination {
+    fn from(pattern: NamePattern) -> Self {
+        Destination::Pattern(pattern)
+    }
+}
+
+/// Single frame message addressed to a [`Destination`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Outgoing {
+    destination: Destination,
+    priority: u8,
+    pgn: Pgn,
+    data: [u8; 8],
+    len: usize,
+}
+
+impl Outgoing {
+    /// Create a message for `destination`.
+    ///
+    /// Only PDU1 parameter groups can be sent to a single destination, and
+    /// the payload has to fit in one frame.
+    pub fn new(
+        destination: impl Into<Destination>,
+        priority: u8,
+        pgn: Pgn,
+        data: &[u8],
+    ) -> Result<Self, NetworkError> {
+        let valid = pgn.is_pdu1()
+            && data.len() <= 8
+            && J1939Id::new(priority, pgn, Address::new(0), Address::NULL)
+                .is_ok();
+        if !valid {
+            return Err(NetworkError::InvalidMessage);
+        }
+
+        let mut buffer = [0; 8];
+        buffer[..data.len()].copy_from_slice(data);
+
+        Ok(Self {
+            destination: destination.into(),
+            priority,
+            pgn,
+            data: buffer,
+            len: data.len(),
+        })
+    }
+
+    /// Controller application the message is for.
+    pub fn destination(&self) -> Destination {
+        self.destination
+    }
+
+    /// Parameter group of the message.
+    pub fn pgn(&self) -> Pgn {
+        self.pgn
+    }
+
+    /// Message payload.
+    pub fn data(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+
+    /// Identifier addressed to the current address of the destination.
+    pub fn id(
+        &self,
+        table: &AddressTable,
+        source_address: Address,
+    ) -> Result<J1939Id, NetworkError> {
+        let destination_address = self
+            .destination
+            .resolve(table)
+            .ok_or(NetworkError::Unresolved)?;
+
+        // checked in `new`
+        Ok(J1939Id::new(
+            self.priority,
+            self.pgn,
+            destination_address,
+            source_address,
+        )
+        .unwrap())
+    }
+
+    /// Create a frame addressed to the current address of the destination.
+    ///
+    /// Fails with [`NetworkError::Unresolved`] while no controller
+    /// application matching the destination holds an address, for example
+    /// while it is re-claiming after losing its address.
+    pub fn to_frame<F: Frame>(
+        &self,
+        table: &AddressTable,
+        source_address: Address,
+    ) -> Result<F, NetworkError> {
+        let id = self.id(table, source_address)?;
+
+        F::new(id, self.data()).ok_or(NetworkError::InvalidMessage)
+    }
+}
+
+/// Result of polling a [`SendQueue`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Delivery<F> {
+    /// Frame to send now.
+    Send(F),
+    /// Message whose destination did not get an address in time.
+    Expired(Outgoing),
+    /// Message that could not be made into a frame.
+    Failed(Outgoing, NetworkError),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Queued {
+    message: Outgoing,
+    order: u32,
+    until: Instant,
+}
+
+/// Holds messages until their destination has an address.
+///
+/// Messages are released in the order they were queued once their
+/// destination resolves, and handed back if it does not resolve before the
+/// timeout.
+#[derive(Debug, Clone)]
+pub struct SendQueue<const N: usize> {
+    queue: [Option<Queued>; N],
+    timeout: Duration,
+    order: u32,
+}
+
+impl<const N: usize> SendQueue<N> {
+    /// Create an empty queue, giving up on messages after `timeout`.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            queue: [None; N],
+            timeout,
+            order: 0,
+        }
+    }
+
+    /// Number of queued messages.
+    pub fn len(&self) -> usize {
+        self.queue.iter().flatten().count()
+    }
+
+    /// No messages are queued.
+    pub fn is_empty(&self) -> bool {
+        self.queue.iter().all(Option::is_none)
+    }
+
+    /// Queue a message.
+    pub fn push(
+        &mut self,
+        now: Instant,
+        message: Outgoing,
+    ) -> Result<(), NetworkError> {
+        let slot = self
+            .queue
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(NetworkError::QueueFull)?;
+
+        *slot = Some(Queued {
+            message,
+            order: self.order,
+            until: now + self.timeout,
+        });
+        self.order = self.order.wrapping_add(1);
+
+        Ok(())
+    }
+
+    /// Next frame to send or message to give up on.
+    ///
+    /// Call repeatedly until `None` is returned.
+    pub fn poll<F: Frame>(
+        &mut self,
+        now: Instant,
+        table: &AddressTable,
+        source_address: Address,
+    ) -> Option<Delivery<F>> {
+        let slot = self
+            .queue
+            .iter_mut()
+            .filter(|slot| {
+                slot.is_some_and(|queued| {
+                    now >= queued.until
+                        || queued.message.destination.resolve(table).is_some()
+                })
+            })
+            .min_by_key(|slot| {
+                // filtered on being occupied above
+                let order = slot.unwrap().order;
+                order.wrapping_sub(self.order)
+            })?;
+
+        // filtered on being occupied above
+        let queued = slot.take().unwrap();
+
+        match queued.message.to_frame(table, source_address) {
+            Ok(frame) => Some(Delivery::Send(frame)),
+            Err(NetworkError::Unresolved) => {
+                Some(Delivery::Expired(queued.message))
+            }
+            Err(error) => Some(Delivery::Failed(queued.message, error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::J1939Frame;
+    use crate::network::table::AddressEvent;
+    use crate::network::AddressClaimed;
+    use crate::testing::{at, TestFrame};
+    use embedded_can::Id;
+
+    const PGN: Pgn = Pgn::from_parts(false, false, 0xEF, 0);
+
+    fn engine(function_instance: u8) -> Name {
+        Name::new(
+            10 + function_instance as u32,
+            0x7FF,
+            0,
+            function_instance,
+            0,
+            0,
+            0,
+            IndustryGroup::OnHighway,
+            true,
+        )
+        .unwrap()
+    }
+
+    fn claim(
+        table: &mut AddressTable,
+        millis: u64,
+        claim: AddressClaimed,
+    ) -> Option<AddressEvent> {
+        let frame: TestFrame = claim.to_frame().unwrap();
+        table.receive(at(millis), &J1939Frame::new(&frame).unwrap())
+    }
+
+    #[test]
+    fn pattern() {
+        let pattern = NamePattern::new()
+            .with_function_instance(1)
+            .with_industry_group(IndustryGroup::OnHighway);
+
+        assert!(pattern.matches(engine(1)));
+        assert!(!pattern.matches(engine(0)));
+        assert!(NamePattern::new().matches(engine(0)));
+        assert!(!pattern
+            .with_industry_group(IndustryGroup::Marine)
+            .matches(engine(1)));
+    }
+
+    #[test]
+    fn resolve() {
+        let mut table = AddressTable::new();
+        claim(&mut table, 0, AddressClaimed::new(engine(0), 0x00.into()));
+        claim(&mut table, 0, AddressClaimed::new(engine(1), 0x01.into()));
+
+        let pattern = NamePattern::new().with_function_instance(1);
+        assert_eq!(
+            Destination::from(pattern).resolve(&table),
+            Some(Address::new(0x01))
+        );
+        let message = Outgoing::new(pattern, 6, PGN, &[1, 2, 3]).unwrap();
+        let frame: TestFrame =
+            message.to_frame(&table, Address::new(0x80)).unwrap();
+        let frame = J1939Frame::new(&frame).unwrap();
+        assert_eq!(frame.id().as_raw(), 0x18EF_0180);
+        assert_eq!(frame.data(), &[1, 2, 3]);
+
+        // engine #1 moves
+        claim(&mut table, 10, AddressClaimed::new(engine(1), 0x90.into()));
+        let id = message.id(&table, Address::new(0x80)).unwrap();
+        assert_eq!(id.destination_address(), Address::new(0x90));
+
+        let message = Outgoing::new(engine(2), 6, PGN, &[]).unwrap();
+        assert_eq!(
+            message.to_frame::<TestFrame>(&table, Address::new(0x80)),
+            Err(NetworkError::Unresolved)
+        );
+
+        // broadcast only and oversized messages
+        let pgn = Pgn::from_parts(false, false, 0xFE, 0xF1);
+        assert_eq!(
+            Outgoing::new(engine(0), 6, pgn, &[]),
+            Err(NetworkError::InvalidMessage)
+        );
+        assert_eq!(
+            Outgoing::new(engine(0), 6, PGN, &[0; 9]),
+            Err(NetworkError::InvalidMessage)
+        );
+        assert_eq!(
+            Outgoing::new(engine(0), 8, PGN, &[]),
+            Err(NetworkError::InvalidMessage)
+        );
+    }
+
+    #[test]
+    fn queue() {
+        let mut table = AddressTable::new();
+        claim(&mut table, 0, AddressClaimed::new(engine(0), 0x00.into()));
+        claim(&mut table, 0, AddressClaimed::new(engine(1), 0x01.into()));
+
+        // engine #1 loses its address and is re-claiming
+        claim(&mut table, 10, AddressClaimed::new(engine(0), 0x01.into()));
+        assert_eq!(table.address_of(engine(1)), None);
+
+        let mut queue = SendQueue::<2>::new(Duration::from_millis(500));
+        let first = Outgoing::new(engine(1), 6, PGN, &[1]).unwrap();
+        let second = Outgoing::new(engine(1), 6, PGN, &[2]).unwrap();
+        queue.push(at(10), first).unwrap();
+        queue.push(at(10), second).unwrap();
+        assert_eq!(queue.push(at(10), first), Err(NetworkError::QueueFull));
+
+        let source = Address::new(0x80);
+        assert_eq!(queue.poll::<TestFrame>(at(100), &table, source), None);
+
+        claim(&mut table, 110, AddressClaimed::new(engine(1), 0x81.into()));
+        for data in [[1], [2]] {
+            let Some(Delivery::Send(frame)) =
+                queue.poll::<TestFrame>(at(120), &table, source)
+            else {
+                panic!("expected a frame");
+            };
+            let frame = J1939Frame::new(&frame).unwrap();
+            assert_eq!(frame.destination_address(), Address::new(0x81));
+            assert_eq!(frame.data(), &data);
+        }
+        assert!(queue.is_empty());
+
+        // destination never comes back
+        queue
+            .push(at(200), Outgoing::new(engine(2), 6, PGN, &[]).unwrap())
+            .unwrap();
+        assert_eq!(queue.poll::<TestFrame>(at(699), &table, source), None);
+        assert!(matches!(
+            queue.poll::<TestFrame>(at(700), &table, source),
+            Some(Delivery::Expired(message)) if message.destination() == engine(2).into()
+        ));
+    }
+
+    /// Frame type of a controller without extended identifiers.
+    struct StandardOnly(TestFrame);
+
+    impl Frame for StandardOnly {
+        fn new(id: impl Into<Id>, data: &[u8]) -> Option<Self> {
+            match id.into() {
+                Id::Standard(id) => TestFrame::new(id, data).map(Self),
+                Id::Extended(_) => None,
+            }
+        }
+
+        fn new_remote(id: impl Into<Id>, dlc: usize) -> Option<Self> {
+            TestFrame::new_remote(id, dlc).map(Self)
+        }
+
+        fn is_extended(&self) -> bool {
+            self.0.is_extended()
+        }
+
+        fn is_remote_frame(&self) -> bool {
+            self.0.is_remote_frame()
+        }
+
+        fn id(&self) -> Id {
+            self.0.id()
+        }
+
+        fn dlc(&self) -> usize {
+            self.0.dlc()
+        }
+
+        fn data(&self) -> &[u8] {
+            self.0.data()
+        }
+    }
+
+    #[test]
+    fn failed() {
+        let mut table = AddressTable::new();
+        claim(&mut table, 0, AddressClaimed::new(engine(0), 0x00.into()));
+
+        let mut queue = SendQueue::<1>::new(Duration::from_millis(500));
+        let message = Outgoing::new(engine(0), 6, PGN, &[1]).unwrap();
+        queue.push(at(0), message).unwrap();
+
+        assert!(matches!(
+            queue.poll::<StandardOnly>(at(10), &table, Address::new(0x80)),
+            Some(Delivery::Failed(failed, NetworkError::InvalidMessage))
+                if failed == message
+        ));
+        assert!(queue.is_empty());
+    }
+}
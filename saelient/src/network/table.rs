@@ -0,0 +1,404 @@
+//! Tracking which controller application uses which address.
+
+use super::{AddressClaimed, CommandedAddress};
+use crate::frame::J1939Frame;
+use crate::identifier::{Address, SourceAddress};
+use crate::name::Name;
+use crate::time::Instant;
+use core::time::Duration;
+use embedded_can::Frame;
+
+/// Change to the address table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressEvent {
+    /// `name` claimed `address`.
+    Claimed {
+        name: Name,
+        address: Address,
+        /// Address `name` used before, if it was known.
+        previous_address: Option<Address>,
+        /// Controller application that held `address` before.
+        replaced: Option<Name>,
+    },
+    /// `name` no longer uses `address`.
+    ///
+    /// It sent cannot claim, lost its address to a lower NAME and has yet to
+    /// claim another, or was not heard from before the timeout.
+    Lost { name: Name, address: Address },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    name: Name,
+    seen: Instant,
+}
+
+/// Live mapping between source addresses and the NAMEs claiming them.
+///
+/// Every received frame is passed to `receive` so address claims update the
+/// table. Commanded addresses arrive with the transport protocol and are
+/// passed to `command` once reassembled.
+#[derive(Debug, Clone)]
+pub struct AddressTable {
+    entries: [Option<Entry>; 254],
+    timeout: Option<Duration>,
+}
+
+impl Default for AddressTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AddressTable {
+    /// Create an empty table.
+    pub fn new() -> Self {
+        Self {
+            entries: [None; 254],
+            timeout: None,
+        }
+    }
+
+    /// Forget controller applications that have sent nothing for `timeout`.
+    ///
+    /// Nodes are not required to send anything periodically, so entries never
+    /// expire unless a timeout is set.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// NAME of the controller application using `address`.
+    pub fn name_of(&self, address: Address) -> Option<Name> {
+        self.entry(address).map(|entry| entry.name)
+    }
+
+    /// Address claimed by `name`.
+    pub fn address_of(&self, name: Name) -> Option<Address> {
+        self.find(|other| other == name)
+    }
+
+    /// Address of the first controller application whose NAME matches
+    /// `predicate`, in address order.
+    pub fn find<P>(&self, mut predicate: P) -> Option<Address>
+    where
+        P: FnMut(Name) -> bool,
+    {
+        self.iter()
+            .find(|&(_, name)| predicate(name))
+            .map(|(address, _)| address)
+    }
+
+    /// All known bindings in address order.
+    pub fn iter(&self) -> impl Iterator<Item = (Address, Name)> + '_ {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(address, entry)| {
+                entry.map(|entry| (Address::new(address as u8), entry.name))
+            })
+    }
+
+    /// Number of known controller applications.
+    pub fn len(&self) -> usize {
+        self.entries.iter().flatten().count()
+    }
+
+    /// No controller applications are known.
+    pub fn is_empty(&self) -> bool {
+        self.entries.iter().all(Option::is_none)
+    }
+
+    /// Forget every binding.
+    pub fn clear(&mut self) {
+        self.entries = [None; 254];
+    }
+
+    /// Expire the next controller application not heard from within the
+    /// timeout.
+    ///
+    /// Call repeatedly until `None` is returned.
+    pub fn poll(&mut self, now: Instant) -> Option<AddressEvent> {
+        let timeout = self.timeout?;
+
+        let (address, entry) =
+            self.entries.iter_mut().enumerate().find(|(_, entry)| {
+                entry.is_some_and(|entry| now >= entry.seen + timeout)
+            })?;
+
+        // found above
+        let name = entry.take().unwrap().name;
+
+        Some(AddressEvent::Lost {
+            name,
+            address: Address::new(address as u8),
+        })
+    }
+
+    /// Process a received frame.
+    ///
+    /// Any frame from a known address counts as a sign of life, address
+    /// claims update the table.
+    pub fn receive<F: Frame>(
+        &mut self,
+        now: Instant,
+        frame: &J1939Frame<'_, F>,
+    ) -> Option<AddressEvent> {
+        if frame.pgn() != AddressClaimed::PGN {
+            if let Some(entry) = self.entry_mut(frame.id().source_address()) {
+                entry.seen = now;
+            }
+            return None;
+        }
+
+        let claim = AddressClaimed::decode(frame.id(), frame.data()).ok()?;
+        let name = claim.name();
+        let address = claim.source_address();
+
+        if claim.is_cannot_claim() {
+            return self.remove(name);
+        }
+
+        match self.entry(address) {
+            Some(holder) if holder.name == name => {
+                // claim repeated, e.g. in answer to a request
+                self.bind(now, name, address);
+                None
+            }
+            Some(holder) if holder.name < name => {
+                // the holder keeps the address, the contender has to look
+                // elsewhere
+                self.remove(name)
+            }
+            _ => self.bind(now, name, address),
+        }
+    }
+
+    /// Process a commanded address.
+    ///
+    /// The commanded controller application is expected to claim the new
+    /// address straight away, so the table is updated without waiting for
+    /// the claim. Commands for unknown NAMEs are ignored.
+    pub fn command(
+        &mut self,
+        now: Instant,
+        command: &CommandedAddress,
+    ) -> Option<AddressEvent> {
+        let name = command.name();
+        let address = command.new_address();
+
+        if !address.is_unicast() || self.address_of(name)? == address {
+            return None;
+        }
+
+        self.bind(now, name, address)
+    }
+
+    /// Bind `name` to `address`, dropping any earlier binding of either.
+    fn bind(
+        &mut self,
+        now: Instant,
+        name: Name,
+        address: Address,
+    ) -> Option<AddressEvent> {
+        let previous_address = self.address_of(name);
+        if let Some(previous) = previous_address {
+            self.entries[previous.as_raw() as usize] = None;
+        }
+
+        // only unicast addresses are claimed
+        let slot = self.entries.get_mut(address.as_raw() as usize)?;
+        let replaced = slot.replace(Entry { name, seen: now });
+
+        if previous_address == Some(address) {
+            return None;
+        }
+
+        Some(AddressEvent::Claimed {
+            name,
+            address,
+            previous_address,
+            replaced: replaced.map(|entry| entry.name),
+        })
+    }
+
+    fn remove(&mut self, name: Name) -> Option<AddressEvent> {
+        let address = self.address_of(name)?;
+        self.entries[address.as_raw() as usize] = None;
+
+        Some(AddressEvent::Lost { name, address })
+    }
+
+    fn entry(&self, address: Address) -> Option<&Entry> {
+        self.entries.get(address.as_raw() as usize)?.as_ref()
+    }
+
+    fn entry_mut(&mut self, address: Address) -> Option<&mut Entry> {
+        self.entries.get_mut(address.as_raw() as usize)?.as_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{at, name, TestFrame};
+
+    fn receive(
+        table: &mut AddressTable,
+        millis: u64,
+        claim: AddressClaimed,
+    ) -> Option<AddressEvent> {
+        let frame: TestFrame = claim.to_frame().unwrap();
+        table.receive(at(millis), &J1939Frame::new(&frame).unwrap())
+    }
+
+    #[test]
+    fn claims() {
+        let mut table = AddressTable::new();
+        let engine = AddressClaimed::new(name(10, true), Address::new(0x00));
+
+        assert_eq!(
+            receive(&mut table, 0, engine),
+            Some(AddressEvent::Claimed {
+                name: name(10, true),
+                address: Address::new(0x00),
+                previous_address: None,
+                replaced: None,
+            })
+        );
+        assert_eq!(receive(&mut table, 10, engine), None);
+        assert_eq!(table.name_of(Address::new(0x00)), Some(name(10, true)));
+        assert_eq!(table.address_of(name(10, true)), Some(Address::new(0x00)));
+
+        // moves to another address
+        let moved = AddressClaimed::new(name(10, true), Address::new(0x80));
+        assert_eq!(
+            receive(&mut table, 20, moved),
+            Some(AddressEvent::Claimed {
+                name: name(10, true),
+                address: Address::new(0x80),
+                previous_address: Some(Address::new(0x00)),
+                replaced: None,
+            })
+        );
+        assert_eq!(table.name_of(Address::new(0x00)), None);
+        assert_eq!(table.len(), 1);
+
+        // a lower NAME takes the address over
+        let winner = AddressClaimed::new(name(9, true), Address::new(0x80));
+        assert_eq!(
+            receive(&mut table, 30, winner),
+            Some(AddressEvent::Claimed {
+                name: name(9, true),
+                address: Address::new(0x80),
+                previous_address: None,
+                replaced: Some(name(10, true)),
+            })
+        );
+        assert_eq!(table.address_of(name(10, true)), None);
+
+        // a higher NAME loses straight away
+        let loser = AddressClaimed::new(name(11, true), Address::new(0x80));
+        assert_eq!(receive(&mut table, 40, loser), None);
+        assert_eq!(table.name_of(Address::new(0x80)), Some(name(9, true)));
+
+        let cannot = AddressClaimed::cannot_claim(name(9, true));
+        assert_eq!(
+            receive(&mut table, 50, cannot),
+            Some(AddressEvent::Lost {
+                name: name(9, true),
+                address: Address::new(0x80),
+            })
+        );
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn lost_contention() {
+        let mut table = AddressTable::new();
+        receive(
+            &mut table,
+            0,
+            AddressClaimed::new(name(9, true), 0x80.into()),
+        );
+        receive(
+            &mut table,
+            0,
+            AddressClaimed::new(name(11, true), 0x81.into()),
+        );
+
+        // moving onto an address held by a lower NAME gives up the old one
+        let loser = AddressClaimed::new(name(11, true), Address::new(0x80));
+        assert_eq!(
+            receive(&mut table, 10, loser),
+            Some(AddressEvent::Lost {
+                name: name(11, true),
+                address: Address::new(0x81),
+            })
+        );
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn command() {
+        let mut table = AddressTable::new();
+        receive(
+            &mut table,
+            0,
+            AddressClaimed::new(name(10, true), 0x25.into()),
+        );
+
+        let command = CommandedAddress::new(name(10, true), Address::new(0x30));
+        assert_eq!(
+            table.command(at(10), &command),
+            Some(AddressEvent::Claimed {
+                name: name(10, true),
+                address: Address::new(0x30),
+                previous_address: Some(Address::new(0x25)),
+                replaced: None,
+            })
+        );
+        assert_eq!(table.command(at(10), &command), None);
+
+        // the claim that follows changes nothing
+        let claim = AddressClaimed::new(name(10, true), Address::new(0x30));
+        assert_eq!(receive(&mut table, 20, claim), None);
+
+        let unknown = CommandedAddress::new(name(11, true), Address::new(0x31));
+        assert_eq!(table.command(at(30), &unknown), None);
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn timeout() {
+        let mut table =
+            AddressTable::new().with_timeout(Duration::from_millis(1000));
+        receive(
+            &mut table,
+            0,
+            AddressClaimed::new(name(10, true), 0x25.into()),
+        );
+        receive(
+            &mut table,
+            0,
+            AddressClaimed::new(name(11, true), 0x26.into()),
+        );
+
+        // any traffic keeps a node alive
+        let frame = crate::testing::frame(0x18FE_F126, &[0; 8]);
+        table.receive(at(900), &J1939Frame::new(&frame).unwrap());
+
+        assert_eq!(table.poll(at(999)), None);
+        assert_eq!(
+            table.poll(at(1000)),
+            Some(AddressEvent::Lost {
+                name: name(10, true),
+                address: Address::new(0x25),
+            })
+        );
+        assert_eq!(table.poll(at(1000)), None);
+        assert_eq!(table.find(|name| name.identity() == 11), Some(0x26.into()));
+        assert!(table.poll(at(1900)).is_some());
+        assert!(table.is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+//! Preferred address information.
+
+use crate::name::IndustryGroup;
+
+/// Returns the controller application a source address is preferred for.
+///
+/// Addresses 0 to 127 and 248 to 255 are assigned the same in every industry
+/// group, the assignment of 128 to 247 depends on the industry group.
+///
+/// Only the global assignments of 0 to 91 and 249 to 255 are extracted so
+/// far, see `scripts/preferred_addresses.txt`. Other addresses return `None`.
+pub const fn preferred_address_name(
+    address: u8,
+    industry_group: IndustryGroup,
+) -> Option<&'static str> {
+    let key = (industry_group, address);
+
+    // generated by scripts/name_tables.py from scripts/preferred_addresses.txt
+    Some(match key {
+        (_, 0) => "Engine #1",
+        (_, 1) => "Engine #2",
+        (_, 2) => "Turbocharger",
+        (_, 3) => "Transmission #1",
+        (_, 4) => "Transmission #2",
+        (_, 5) => "Shift Console - Primary",
+        (_, 6) => "Shift Console - Secondary",
+        (_, 7) => "Power TakeOff - (Main or Rear)",
+        (_, 8) => "Axle - Steering",
+        (_, 9) => "Axle - Drive #1",
+        (_, 10) => "Axle - Drive #2",
+        (_, 11) => "Brakes - System Controller",
+        (_, 12) => "Brakes - Steer Axle",
+        (_, 13) => "Brakes - Drive axle #1",
+        (_, 14) => "Brakes - Drive Axle #2",
+        (_, 15) => "Retarder - Engine",
+        (_, 16) => "Retarder - Driveline",
+        (_, 17) => "Cruise Control",
+        (_, 18) => "Fuel System",
+        (_, 19) => "Steering Controller",
+        (_, 20) => "Suspension - Steer Axle",
+        (_, 21) => "Suspension - Drive Axle #1",
+        (_, 22) => "Suspension - Drive Axle #2",
+        (_, 23) => "Instrument Cluster #1",
+        (_, 24) => "Trip Recorder",
+        (_, 25) => "Passenger-Operator Climate Control #1",
+        (_, 26) => "Alternator/Electrical Charging System",
+        (_, 27) => "Aerodynamic Control",
+        (_, 28) => "Vehicle Navigation",
+        (_, 29) => "Vehicle Security",
+        (_, 30) => "Electrical System",
+        (_, 31) => "Starter System",
+        (_, 32) => "Tractor-Trailer Bridge #1",
+        (_, 33) => "Body Controller",
+        (_, 34) => "Auxiliary Valve Control or Engine Air System Valve Control",
+        (_, 35) => "Hitch Control",
+        (_, 36) => "Power TakeOff (Front or Secondary)",
+        (_, 37) => "Off Vehicle Gateway",
+        (_, 38) => "Virtual Terminal (in cab)",
+        (_, 39) => "Management Computer #1",
+        (_, 40) => "Cab Display #1",
+        (_, 41) => "Retarder, Exhaust, Engine #1",
+        (_, 42) => "Headway Controller",
+        (_, 43) => "On-Board Diagnostic Unit",
+        (_, 44) => "Retarder, Exhaust, Engine #2",
+        (_, 45) => "Endurance Braking System",
+        (_, 46) => "Hydraulic Pump Controller",
+        (_, 47) => "Suspension - System Controller #1",
+        (_, 48) => "Pneumatic - System Controller",
+        (_, 49) => "Cab Controller - Primary",
+        (_, 50) => "Cab Controller - Secondary",
+        (_, 51) => "Tire Pressure Controller",
+        (_, 52) => "Ignition Control Module #1",
+        (_, 53) => "Ignition Control Module #2",
+        (_, 54) => "Seat Control #1",
+        (_, 55) => "Lighting - Operator Controls",
+        (_, 56) => "Rear Axle Steering Controller #1",
+        (_, 57) => "Water Pump Controller",
+        (_, 58) => "Passenger-Operator Climate Control #2",
+        (_, 59) => "Transmission Display - Primary",
+        (_, 60) => "Transmission Display - Secondary",
+        (_, 61) => "Exhaust Emission Controller",
+        (_, 62) => "Vehicle Dynamic Stability Controller",
+        (_, 63) => "Oil Sensor",
+        (_, 64) => "Suspension - System Controller #2",
+        (_, 65) => "Information System Controller #1",
+        (_, 66) => "Ramp Control",
+        (_, 67) => "Clutch/Converter Unit",
+        (_, 68) => "Auxiliary Heater #1",
+        (_, 69) => "Auxiliary Heater #2",
+        (_, 70) => "Engine Valve Controller",
+        (_, 71) => "Chassis Controller #1",
+        (_, 72) => "Chassis Controller #2",
+        (_, 73) => "Propulsion Battery Charger",
+        (_, 74) => "Communications Unit, Cellular",
+        (_, 75) => "Communications Unit, Satellite",
+        (_, 76) => "Communications Unit, Radio",
+        (_, 77) => "Steering Column Unit",
+        (_, 78) => "Fan Drive Controller",
+        (_, 79) => "Seat Control #2",
+        (_, 80) => "Parking Brake Controller",
+        (_, 81) => "Aftertreatment #1 System Gas Intake",
+        (_, 82) => "Aftertreatment #1 System Gas Outlet",
+        (_, 83) => "Safety Restraint System",
+        (_, 84) => "Cab Display #2",
+        (_, 85) => "Diesel Particulate Filter Controller",
+        (_, 86) => "Aftertreatment #2 System Gas Intake",
+        (_, 87) => "Aftertreatment #2 System Gas Outlet",
+        (_, 88) => "Safety Restraint System #2",
+        (_, 89) => "Atmospheric Sensor",
+        (_, 90) => "Powertrain Control Module",
+        (_, 91) => "Power Systems Manager",
+        (_, 249) => "Off Board Diagnostic-Service Tool #1",
+        (_, 250) => "Off Board Diagnostic-Service Tool #2",
+        (_, 251) => "On-Board Data Logger",
+        (_, 252) => "Reserved for Experimental Use",
+        (_, 253) => "Reserved for OEM",
+        (_, 254) => "Null Address",
+        (_, 255) => "Global",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample() {
+        // limit cases
+        assert_eq!(
+            preferred_address_name(0, IndustryGroup::OnHighway),
+            Some("Engine #1")
+        );
+        assert_eq!(
+            preferred_address_name(255, IndustryGroup::OnHighway),
+            Some("Global")
+        );
+        assert_eq!(preferred_address_name(128, IndustryGroup::OnHighway), None);
+
+        // some cases selected at random
+        assert_eq!(
+            preferred_address_name(3, IndustryGroup::OnHighway),
+            Some("Transmission #1")
+        );
+        assert_eq!(
+            preferred_address_name(249, IndustryGroup::OnHighway),
+            Some("Off Board Diagnostic-Service Tool #1")
+        );
+    }
+}
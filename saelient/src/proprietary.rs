@@ -0,0 +1,325 @@
+//! Proprietary messages.
+//!
+//! The content of proprietary parameter groups is defined by the manufacturer
+//! of the sending ECU, so decoding depends on who sent the message. Decoders
+//! are registered against a manufacturer code (see
+//! [`manufacturer_name`](crate::manufacturer::manufacturer_name)) and chosen
+//! using the [`Name`] of the sender.
+
+use crate::error::MessageError;
+use crate::identifier::{Address, Extended, J1939Id, Pgn, SourceAddress};
+use crate::name::Name;
+use embedded_can::ExtendedId;
+
+/// Proprietary parameter group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProprietaryKind {
+    /// Proprietary A (PGN 61184), destination specific.
+    A,
+    /// Proprietary A2 (PGN 126720), destination specific.
+    A2,
+    /// Proprietary B (PGN 65280 to 65535), broadcast with the given group
+    /// extension.
+    B(u8),
+}
+
+impl ProprietaryKind {
+    /// Proprietary A parameter group number.
+    pub const PGN_A: Pgn = Pgn::from_parts(false, false, 0xEF, 0);
+
+    /// Proprietary A2 parameter group number.
+    pub const PGN_A2: Pgn = Pgn::from_parts(false, true, 0xEF, 0);
+
+    /// PDU format of the proprietary B parameter groups.
+    pub const PDU_FORMAT_B: u8 = 0xFF;
+
+    /// Find which proprietary parameter group `pgn` is, if any.
+    pub fn from_pgn(pgn: Pgn) -> Option<Self> {
+        match pgn {
+            Self::PGN_A => Some(Self::A),
+            Self::PGN_A2 => Some(Self::A2),
+            _ if !pgn.data_page()
+                && !pgn.extended_data_page()
+                && pgn.pdu_format() == Self::PDU_FORMAT_B =>
+            {
+                Some(Self::B(pgn.pdu_specific()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Parameter group number.
+    pub fn pgn(&self) -> Pgn {
+        match self {
+            Self::A => Self::PGN_A,
+            Self::A2 => Self::PGN_A2,
+            Self::B(group_extension) => Pgn::from_parts(
+                false,
+                false,
+                Self::PDU_FORMAT_B,
+                *group_extension,
+            ),
+        }
+    }
+}
+
+/// Proprietary message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Proprietary<'a> {
+    kind: ProprietaryKind,
+    data: &'a [u8],
+    destination_address: Address,
+    source_address: Address,
+}
+
+impl<'a> Proprietary<'a> {
+    /// Largest payload, limited by the transport protocol.
+    pub const MAX_LEN: usize = 1785;
+
+    /// Default priority for proprietary messages.
+    pub const PRIORITY: u8 = 6;
+
+    /// Create a new proprietary message.
+    ///
+    /// Proprietary B messages are broadcast so `destination_address` must be
+    /// [`Address::GLOBAL`].
+    pub fn new(
+        kind: ProprietaryKind,
+        data: &'a [u8],
+        destination_address: Address,
+        source_address: Address,
+    ) -> Result<Self, MessageError> {
+        if data.len() > Self::MAX_LEN {
+            return Err(MessageError::InvalidLength);
+        }
+
+        if matches!(kind, ProprietaryKind::B(_))
+            && !destination_address.is_global()
+        {
+            return Err(MessageError::DestinationNotGlobal);
+        }
+
+        Ok(Self {
+            kind,
+            data,
+            destination_address,
+            source_address,
+        })
+    }
+
+    /// Decode a proprietary message from its identifier and payload.
+    pub fn decode(
+        id: ExtendedId,
+        data: &'a [u8],
+    ) -> Result<Self, MessageError> {
+        let kind = ProprietaryKind::from_pgn(id.pgn())
+            .ok_or(MessageError::UnexpectedPgn)?;
+
+        if data.len() > Self::MAX_LEN {
+            return Err(MessageError::InvalidLength);
+        }
+
+        Ok(Self {
+            kind,
+            data,
+            destination_address: id
+                .destination_address()
+                .unwrap_or(Address::GLOBAL),
+            source_address: id.source_address(),
+        })
+    }
+
+    /// Identifier to send the message with.
+    pub fn id(&self) -> J1939Id {
+        // PDU2 destination is checked in `new`
+        J1939Id::new(
+            Self::PRIORITY,
+            self.kind.pgn(),
+            self.destination_address,
+            self.source_address,
+        )
+        .unwrap()
+    }
+
+    /// Which proprietary parameter group the message belongs to.
+    pub fn kind(&self) -> ProprietaryKind {
+        self.kind
+    }
+
+    /// Manufacturer specific payload.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Node the message is sent to, global for proprietary B.
+    pub fn destination_address(&self) -> Address {
+        self.destination_address
+    }
+
+    /// Node sending the message.
+    pub fn source_address(&self) -> Address {
+        self.source_address
+    }
+}
+
+/// Function decoding a proprietary message into a user defined type.
+pub type Decoder<T> = fn(&Proprietary<'_>) -> Option<T>;
+
+/// Proprietary decoders keyed by manufacturer code.
+///
+/// Holds up to `N` decoders. More than one decoder can be registered for the
+/// same manufacturer, they are tried in the order they were registered.
+#[derive(Debug)]
+pub struct Decoders<T, const N: usize> {
+    decoders: [Option<(u16, Decoder<T>)>; N],
+}
+
+impl<T, const N: usize> Default for Decoders<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Decoders<T, N> {
+    /// Create an empty set of decoders.
+    pub fn new() -> Self {
+        Self {
+            decoders: [None; N],
+        }
+    }
+
+    /// Register a decoder for messages sent by ECUs from `manufacturer_code`.
+    ///
+    /// Gives the decoder back if there is no room left.
+    pub fn register(
+        &mut self,
+        manufacturer_code: u16,
+        decoder: Decoder<T>,
+    ) -> Result<(), Decoder<T>> {
+        match self.decoders.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some((manufacturer_code, decoder));
+                Ok(())
+            }
+            None => Err(decoder),
+        }
+    }
+
+    /// Decode `message` sent by the ECU identified by `name`.
+    ///
+    /// Returns `None` if no registered decoder understands the message.
+    pub fn decode(&self, name: &Name, message: &Proprietary<'_>) -> Option<T> {
+        let manufacturer_code = name.manufacturer_code();
+
+        self.decoders
+            .iter()
+            .flatten()
+            .filter(|(code, _)| *code == manufacturer_code)
+            .find_map(|(_, decoder)| decoder(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manufacturer::manufacturer_name;
+    use crate::name::IndustryGroup;
+
+    #[derive(Debug, PartialEq)]
+    enum Decoded {
+        Speed(u16),
+        Mode(u8),
+    }
+
+    fn speed(message: &Proprietary<'_>) -> Option<Decoded> {
+        match (message.kind(), message.data()) {
+            (ProprietaryKind::B(0x10), [low, high, ..]) => {
+                Some(Decoded::Speed(u16::from_le_bytes([*low, *high])))
+            }
+            _ => None,
+        }
+    }
+
+    fn mode(message: &Proprietary<'_>) -> Option<Decoded> {
+        match (message.kind(), message.data()) {
+            (ProprietaryKind::A, [mode, ..]) => Some(Decoded::Mode(*mode)),
+            _ => None,
+        }
+    }
+
+    fn name(manufacturer_code: u16) -> Name {
+        Name::new(
+            1,
+            manufacturer_code,
+            0,
+            0,
+            0,
+            0,
+            0,
+            IndustryGroup::Global,
+            false,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn kinds() {
+        let id = ExtendedId::new(0x18EF_21F9).unwrap();
+        let message = Proprietary::decode(id, &[1, 2, 3]).unwrap();
+        assert_eq!(message.kind(), ProprietaryKind::A);
+        assert_eq!(message.destination_address(), Address::new(0x21));
+        assert_eq!(message.id().as_raw(), 0x18EF_21F9);
+
+        let id = ExtendedId::new(0x19EF_21F9).unwrap();
+        let message = Proprietary::decode(id, &[1, 2, 3]).unwrap();
+        assert_eq!(message.kind(), ProprietaryKind::A2);
+        assert_eq!(message.kind().pgn().as_raw(), 126720);
+
+        let id = ExtendedId::new(0x18FF_10F9).unwrap();
+        let message = Proprietary::decode(id, &[1, 2, 3]).unwrap();
+        assert_eq!(message.kind(), ProprietaryKind::B(0x10));
+        assert_eq!(message.destination_address(), Address::GLOBAL);
+        assert_eq!(message.id().as_raw(), 0x18FF_10F9);
+
+        let id = ExtendedId::new(0x18FE_CAF9).unwrap();
+        assert_eq!(
+            Proprietary::decode(id, &[1, 2, 3]),
+            Err(MessageError::UnexpectedPgn)
+        );
+
+        assert_eq!(
+            Proprietary::new(
+                ProprietaryKind::B(0),
+                &[],
+                Address::new(0x21),
+                Address::new(0xF9)
+            ),
+            Err(MessageError::DestinationNotGlobal)
+        );
+    }
+
+    #[test]
+    fn decoders() {
+        let mut decoders = Decoders::<Decoded, 2>::new();
+        decoders.register(1863, speed).unwrap();
+        decoders.register(1863, mode).unwrap();
+        assert!(decoders.register(8, speed).is_err());
+
+        assert_eq!(manufacturer_name(1863), Some("Thomas G. Faria Co."));
+
+        let id = ExtendedId::new(0x18FF_10F9).unwrap();
+        let message = Proprietary::decode(id, &[0x34, 0x12]).unwrap();
+        assert_eq!(
+            decoders.decode(&name(1863), &message),
+            Some(Decoded::Speed(0x1234))
+        );
+        assert_eq!(decoders.decode(&name(8), &message), None);
+
+        let id = ExtendedId::new(0x18EF_21F9).unwrap();
+        let message = Proprietary::decode(id, &[0x02]).unwrap();
+        assert_eq!(
+            decoders.decode(&name(1863), &message),
+            Some(Decoded::Mode(2))
+        );
+    }
+}
@@ -1,77 +1,67 @@
 //! Parameter signals.
 
+use crate::error::SignalError;
 use core::ops::RangeInclusive;
-use std::convert::TryFrom;
 
 /// Transmitted values for distrete parameters (measured).
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Default, PartialEq)]
 pub enum Parameter {
     Disabled = 0x0,
     Enabled = 0x1,
     IsError = 0x2,
+    #[default]
     NotAvailable = 0x3,
 }
 
-impl Default for Parameter {
-    fn default() -> Self {
-        Self::NotAvailable
-    }
-}
-
-impl Into<u8> for Parameter {
-    fn into(self) -> u8 {
-        self as u8
+impl From<Parameter> for u8 {
+    fn from(value: Parameter) -> u8 {
+        value as u8
     }
 }
 
 impl TryFrom<u8> for Parameter {
-    type Error = ();
+    type Error = SignalError;
 
-    fn try_from(value: u8) -> Result<Self, ()> {
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
             0x0 => Ok(Parameter::Disabled),
             0x1 => Ok(Parameter::Enabled),
             0x2 => Ok(Parameter::IsError),
             0x3 => Ok(Parameter::NotAvailable),
-            _ => Err(()),
+            _ => Err(SignalError::InvalidValue),
         }
     }
 }
 
 /// Transmitted values for control commands (status).
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Default, PartialEq)]
 pub enum Command {
     Disable = 0x0,
     Enable = 0x1,
     // 0x2 reserved
+    #[default]
     NoAction = 0x3,
 }
 
-impl Into<u8> for Command {
-    fn into(self) -> u8 {
-        self as u8
+impl From<Command> for u8 {
+    fn from(value: Command) -> u8 {
+        value as u8
     }
 }
 
 impl TryFrom<u8> for Command {
-    type Error = ();
+    type Error = SignalError;
 
-    fn try_from(value: u8) -> Result<Self, ()> {
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
             0x0 => Ok(Command::Disable),
             0x1 => Ok(Command::Enable),
             0x3 => Ok(Command::NoAction),
-            _ => Err(()),
+            _ => Err(SignalError::InvalidValue),
         }
     }
 }
 
-impl Default for Command {
-    fn default() -> Self {
-        Self::NoAction
-    }
-}
-
 pub trait Signal<T> {
     /// The size of this type in bites.
     const BITS: u8;
@@ -88,7 +78,7 @@ pub trait Signal<T> {
     const NOT_AVAILABLE_RANGE: RangeInclusive<T>;
 
     /// Creates a new instance from the given value.
-    fn new(value: T) -> Option<Self>
+    fn new(value: T) -> Result<Self, SignalError>
     where
         Self: Sized;
 
@@ -121,11 +111,11 @@ macro_rules! signal {
                 const ERROR_RANGE: RangeInclusive<$TYPE> = $error;
                 const NOT_AVAILABLE_RANGE: RangeInclusive<$TYPE> = $notavailable;
 
-                fn new(value: $TYPE) -> Option<Self> {
+                fn new(value: $TYPE) -> Result<Self, SignalError> {
                     if (&value <= Self::NOT_AVAILABLE_RANGE.end()) {
-                        Some(Self(value))
+                        Ok(Self(value))
                     } else {
-                        None
+                        Err(SignalError::OutOfRange)
                     }
                 }
 
@@ -231,13 +221,19 @@ mod tests {
     fn param_failed_conversion() {
         let parameter = Parameter::try_from(99);
 
-        assert_eq!(parameter, Err(()))
+        assert_eq!(parameter, Err(SignalError::InvalidValue))
     }
 
     #[test]
     fn cmd_failed_conversion() {
         let command = Command::try_from(99);
 
-        assert_eq!(command, Err(()))
+        assert_eq!(command, Err(SignalError::InvalidValue))
+    }
+
+    #[test]
+    fn signal_out_of_range() {
+        assert!(U4::new(0xF).is_ok());
+        assert!(matches!(U4::new(0x10), Err(SignalError::OutOfRange)));
     }
 }
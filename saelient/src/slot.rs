@@ -1,16 +1,17 @@
 //! Transfer functions.
 
+use crate::error::SlotError;
 use crate::Float;
 
 /// Scaling limit offset transfer function.
 pub trait Slot {
     /// Tries to create a `Slot` from a value.
-    fn new(value: Float) -> Option<Self>
+    fn new(value: Float) -> Result<Self, SlotError>
     where
         Self: Sized;
 
     /// Construct a `Slot` from the raw value.
-    fn from_raw(raw: Float) -> Option<Self>
+    fn from_raw(raw: Float) -> Result<Self, SlotError>
     where
         Self: Sized;
 
@@ -37,28 +38,26 @@ macro_rules! slot {
 
         impl Slot for $name {
             #[inline]
-            fn new(value: Float) -> Option<Self> {
+            fn new(value: Float) -> Result<Self, SlotError> {
                 let (min, max) = Self::limits();
 
-                if value >= min && value <= max {
-                    Some(Self { value })
+                if value.is_nan() {
+                    Err(SlotError::NotANumber)
+                } else if value < min {
+                    Err(SlotError::BelowMinimum)
+                } else if value > max {
+                    Err(SlotError::AboveMaximum)
                 } else {
-                    None
+                    Ok(Self { value })
                 }
             }
 
             #[inline]
-            fn from_raw(raw: Float) -> Option<Self> {
+            fn from_raw(raw: Float) -> Result<Self, SlotError> {
                 let value =
                     ((raw as Float) * $name::scaling()) + $name::offset();
 
-                let (min, max) = $name::limits();
-
-                if value >= min && value >= max {
-                    Some(Self { value })
-                } else {
-                    None
-                }
+                Self::new(value)
             }
 
             #[inline]
@@ -114,6 +113,16 @@ mod tests {
     fn test_slot() {
         assert_eq!(SAEaa01::scaling(), 1.0);
 
-        assert!(SAEaa01::new(0.0).is_some());
+        assert!(SAEaa01::new(0.0).is_ok());
+        assert!(SAEaa01::from_raw(100.0).is_ok());
+        assert!(matches!(SAEaa01::new(-1.0), Err(SlotError::BelowMinimum)));
+        assert!(matches!(
+            SAEaa01::new(64256.0),
+            Err(SlotError::AboveMaximum)
+        ));
+        assert!(matches!(
+            SAEaa01::new(Float::NAN),
+            Err(SlotError::NotANumber)
+        ));
     }
 }
@@ -0,0 +1,121 @@
+//! Helpers shared by unit tests.
+
+use crate::name::{IndustryGroup, Name};
+use crate::time::Instant;
+use crate::transport::{ConnectionManagement, Packet};
+use embedded_can::{ExtendedId, Frame, Id};
+
+/// Minimal classic CAN frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TestFrame {
+    id: Id,
+    remote: bool,
+    dlc: usize,
+    data: [u8; 8],
+}
+
+impl Frame for TestFrame {
+    fn new(id: impl Into<Id>, data: &[u8]) -> Option<Self> {
+        if data.len() > 8 {
+            return None;
+        }
+
+        let mut frame = Self {
+            id: id.into(),
+            remote: false,
+            dlc: data.len(),
+            data: [0; 8],
+        };
+        frame.data[..data.len()].copy_from_slice(data);
+        Some(frame)
+    }
+
+    fn new_remote(id: impl Into<Id>, dlc: usize) -> Option<Self> {
+        if dlc > 8 {
+            return None;
+        }
+
+        Some(Self {
+            id: id.into(),
+            remote: true,
+            dlc,
+            data: [0; 8],
+        })
+    }
+
+    fn is_extended(&self) -> bool {
+        matches!(self.id, Id::Extended(_))
+    }
+
+    fn is_remote_frame(&self) -> bool {
+        self.remote
+    }
+
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn dlc(&self) -> usize {
+        self.dlc
+    }
+
+    fn data(&self) -> &[u8] {
+        if self.remote {
+            &[]
+        } else {
+            &self.data[..self.dlc]
+        }
+    }
+}
+
+/// Create a data frame with an extended identifier.
+pub fn frame(id: u32, data: &[u8]) -> TestFrame {
+    TestFrame::new(ExtendedId::new(id).unwrap(), data).unwrap()
+}
+
+/// Create a frame from a transport packet.
+pub fn packet_frame(packet: Packet) -> TestFrame {
+    packet.to_frame().unwrap()
+}
+
+/// Decode the connection management message of a transport packet.
+pub fn control(packet: &Packet) -> ConnectionManagement {
+    ConnectionManagement::decode(packet.data()).unwrap()
+}
+
+/// Instant `millis` milliseconds after the start.
+pub fn at(millis: u64) -> Instant {
+    Instant::from_millis(millis)
+}
+
+/// Create an on-highway NAME told apart by its identity.
+pub fn name(identity: u32, arbitrary_address_capable: bool) -> Name {
+    Name::new(
+        identity,
+        0x7FF,
+        0,
+        0,
+        0,
+        0,
+        0,
+        IndustryGroup::OnHighway,
+        arbitrary_address_capable,
+    )
+    .unwrap()
+}
+
+/// Create a construction NAME told apart by its ECU and function instance.
+pub fn instance_name(ecu_instance: u8, function_instance: u8) -> Name {
+    Name::new(
+        170254,
+        29,
+        ecu_instance,
+        function_instance,
+        128,
+        4,
+        2,
+        IndustryGroup::Construction,
+        true,
+    )
+    .unwrap()
+}
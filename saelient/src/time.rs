@@ -0,0 +1,58 @@
+//! Caller supplied time.
+//!
+//! State machines in this crate never read a clock themselves. Instead the
+//! current time is passed in with every call, which keeps them usable from
+//! bare-metal main loops, RTOS tasks and tests alike.
+
+use core::ops::Add;
+use core::time::Duration;
+
+/// Point in time with millisecond resolution.
+///
+/// The epoch is up to the caller, usually the time the device started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Instant(u64);
+
+impl Instant {
+    /// Create an `Instant` from milliseconds since the epoch.
+    pub const fn from_millis(millis: u64) -> Self {
+        Self(millis)
+    }
+
+    /// Milliseconds since the epoch.
+    pub const fn as_millis(&self) -> u64 {
+        self.0
+    }
+
+    /// Time elapsed from `earlier` to `self`, zero if `earlier` is later.
+    pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+        Duration::from_millis(self.0.saturating_sub(earlier.0))
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, rhs: Duration) -> Instant {
+        Instant(self.0.saturating_add(rhs.as_millis() as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic() {
+        let start = Instant::from_millis(1000);
+        let later = start + Duration::from_millis(750);
+
+        assert_eq!(later.as_millis(), 1750);
+        assert_eq!(
+            later.saturating_duration_since(start),
+            Duration::from_millis(750)
+        );
+        assert_eq!(start.saturating_duration_since(later), Duration::ZERO);
+    }
+}
@@ -0,0 +1,470 @@
+//! Broadcast announce message.
+
+use super::{
+    packets, ConnectionManagement, DataTransfer, Packet, Reassembled,
+    BAM_MAX_INTERVAL, BAM_MIN_INTERVAL, CONNECTION_MANAGEMENT, DATA_TRANSFER,
+    MAX_LEN, PACKET_LEN, T1,
+};
+use crate::error::TransportError;
+use crate::frame::J1939Frame;
+use crate::identifier::{Address, Pgn};
+use crate::time::Instant;
+use core::time::Duration;
+use embedded_can::Frame;
+
+/// Sends a message to the global address.
+///
+/// The announcement and each data packet are returned one at a time by
+/// [`poll`](Self::poll), spaced at least the configured interval apart.
+#[derive(Debug, Clone)]
+pub struct BamSender<'a> {
+    pgn: Pgn,
+    data: &'a [u8],
+    source_address: Address,
+    interval: Duration,
+    /// Sequence number of the next packet, zero before the announcement.
+    ///
+    /// Wider than the sequence number on the wire so that it can go past the
+    /// last of 255 packets.
+    next: u16,
+    due: Instant,
+}
+
+impl<'a> BamSender<'a> {
+    /// Create a sender for `data`, paced at the shortest allowed interval.
+    pub fn new(
+        pgn: Pgn,
+        data: &'a [u8],
+        source_address: Address,
+    ) -> Result<Self, TransportError> {
+        if data.len() > MAX_LEN {
+            return Err(TransportError::TooLarge);
+        }
+
+        if data.len() <= PACKET_LEN + 1 {
+            return Err(TransportError::InvalidMessage);
+        }
+
+        Ok(Self {
+            pgn,
+            data,
+            source_address,
+            interval: BAM_MIN_INTERVAL,
+            next: 0,
+            due: Instant::from_millis(0),
+        })
+    }
+
+    /// Set the time between packets.
+    ///
+    /// The interval is clamped to between [`BAM_MIN_INTERVAL`] and
+    /// [`BAM_MAX_INTERVAL`].
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval.clamp(BAM_MIN_INTERVAL, BAM_MAX_INTERVAL);
+        self
+    }
+
+    /// Whether all packets have been sent.
+    pub fn is_complete(&self) -> bool {
+        self.next as usize > packets(self.data.len())
+    }
+
+    /// When the next packet is due, `None` once all packets have been sent.
+    pub fn poll_at(&self) -> Option<Instant> {
+        (!self.is_complete()).then_some(self.due)
+    }
+
+    /// Next frame to send, if one is due.
+    pub fn poll(&mut self, now: Instant) -> Option<Packet> {
+        if self.is_complete() || now < self.due {
+            return None;
+        }
+
+        let data = if self.next == 0 {
+            ConnectionManagement::Bam {
+                size: self.data.len() as u16,
+                packets: packets(self.data.len()) as u8,
+                pgn: self.pgn,
+            }
+            .encode()
+        } else {
+            let start = (self.next as usize - 1) * PACKET_LEN;
+            let end = (start + PACKET_LEN).min(self.data.len());
+            DataTransfer::encode(self.next as u8, &self.data[start..end])
+        };
+
+        let pgn = if self.next == 0 {
+            CONNECTION_MANAGEMENT
+        } else {
+            DATA_TRANSFER
+        };
+
+        self.next += 1;
+        self.due = now + self.interval;
+
+        Some(Packet::new(pgn, data, Address::GLOBAL, self.source_address))
+    }
+}
+
+/// Broadcast being received.
+#[derive(Debug, Clone, Copy)]
+struct Session {
+    pgn: Pgn,
+    source_address: Address,
+    size: usize,
+    packets: u8,
+    next: u8,
+    deadline: Instant,
+}
+
+/// Receives a single broadcast at a time.
+///
+/// Messages are reassembled into an internal buffer of `N` bytes, which
+/// defaults to the largest transport protocol message.
+#[derive(Debug, Clone)]
+pub struct BamReceiver<const N: usize = MAX_LEN> {
+    buffer: [u8; N],
+    session: Option<Session>,
+}
+
+impl<const N: usize> Default for BamReceiver<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> BamReceiver<N> {
+    /// Create an idle receiver.
+    pub const fn new() -> Self {
+        Self {
+            buffer: [0; N],
+            session: None,
+        }
+    }
+
+    /// Source address of the broadcast being received, if any.
+    pub fn source_address(&self) -> Option<Address> {
+        self.session.map(|session| session.source_address)
+    }
+
+    /// Parameter group of the broadcast being received, if any.
+    pub fn pgn(&self) -> Option<Pgn> {
+        self.session.map(|session| session.pgn)
+    }
+
+    /// Drop the broadcast being received if it timed out.
+    ///
+    /// Returns [`TransportError::Timeout`] when a broadcast was dropped.
+    pub fn poll(&mut self, now: Instant) -> Result<(), TransportError> {
+        match self.session {
+            Some(session) if now > session.deadline => {
+                self.session = None;
+                Err(TransportError::Timeout)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Process a received frame.
+    ///
+    /// Frames that are not part of a broadcast are ignored. Returns the
+    /// message once its last packet has been received.
+    pub fn receive<F: Frame>(
+        &mut self,
+        now: Instant,
+        frame: &J1939Frame<'_, F>,
+    ) -> Result<Option<Reassembled<'_>>, TransportError> {
+        if !frame.destination_address().is_global() {
+            return Ok(None);
+        }
+
+        let timed_out = self.poll(now).is_err();
+
+        match frame.pgn() {
+            CONNECTION_MANAGEMENT => {
+                self.connection_management(now, frame)?;
+                Ok(None)
+            }
+            DATA_TRANSFER if timed_out => Err(TransportError::Timeout),
+            DATA_TRANSFER => self.data_transfer(now, frame),
+            _ => Ok(None),
+        }
+    }
+
+    fn connection_management<F: Frame>(
+        &mut self,
+        now: Instant,
+        frame: &J1939Frame<'_, F>,
+    ) -> Result<(), TransportError> {
+        let (size, packet_count, pgn) =
+            match ConnectionManagement::decode(frame.data()) {
+                Ok(ConnectionManagement::Bam { size, packets, pgn }) => {
+                    (size as usize, packets, pgn)
+                }
+                _ => return Ok(()),
+            };
+
+        let source_address = frame.source_address();
+
+        match self.session {
+            Some(session) if session.source_address != source_address => {
+                return Err(TransportError::Busy);
+            }
+            // a new broadcast from the same node replaces the old one
+            _ => self.session = None,
+        }
+
+        if size == 0 || size > MAX_LEN || packets(size) != packet_count as usize
+        {
+            return Err(TransportError::InvalidMessage);
+        }
+
+        if size > N {
+            return Err(TransportError::TooLarge);
+        }
+
+        self.session = Some(Session {
+            pgn,
+            source_address,
+            size,
+            packets: packet_count,
+            next: 1,
+            deadline: now + T1,
+        });
+
+        Ok(())
+    }
+
+    fn data_transfer<F: Frame>(
+        &mut self,
+        now: Instant,
+        frame: &J1939Frame<'_, F>,
+    ) -> Result<Option<Reassembled<'_>>, TransportError> {
+        let session = match &mut self.session {
+            Some(session)
+                if session.source_address == frame.source_address() =>
+            {
+                session
+            }
+            _ => return Ok(None),
+        };
+
+        let packet = DataTransfer::new(frame.data())?;
+
+        if packet.sequence() != session.next {
+            self.session = None;
+            return Err(TransportError::BadSequence);
+        }
+
+        let start = (packet.sequence() as usize - 1) * PACKET_LEN;
+        let end = (start + PACKET_LEN).min(session.size);
+        self.buffer[start..end].copy_from_slice(&packet.data()[..end - start]);
+
+        if packet.sequence() < session.packets {
+            session.next += 1;
+            session.deadline = now + T1;
+            return Ok(None);
+        }
+
+        let session = *session;
+        self.session = None;
+
+        Ok(Some(Reassembled {
+            pgn: session.pgn,
+            source_address: session.source_address,
+            destination_address: Address::GLOBAL,
+            data: &self.buffer[..session.size],
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{at, frame, TestFrame};
+
+    const DM1: [u8; 10] =
+        [0x40, 0xFF, 0x9D, 0x00, 0x03, 0x01, 0x9E, 0x00, 0x03, 0x01];
+
+    #[test]
+    fn dm1() {
+        let mut receiver = BamReceiver::<32>::new();
+
+        let bam = frame(0x1CEC_FF00, &[0x20, 10, 0, 2, 0xFF, 0xCA, 0xFE, 0x00]);
+        let dt1 =
+            frame(0x1CEB_FF00, &[1, 0x40, 0xFF, 0x9D, 0x00, 0x03, 0x01, 0x9E]);
+        let dt2 =
+            frame(0x1CEB_FF00, &[2, 0x00, 0x03, 0x01, 0xFF, 0xFF, 0xFF, 0xFF]);
+
+        let view = J1939Frame::new(&bam).unwrap();
+        assert_eq!(receiver.receive(at(0), &view), Ok(None));
+        assert_eq!(receiver.source_address(), Some(Address::new(0x00)));
+
+        let view = J1939Frame::new(&dt1).unwrap();
+        assert_eq!(receiver.receive(at(60), &view), Ok(None));
+
+        let view = J1939Frame::new(&dt2).unwrap();
+        let message = receiver.receive(at(120), &view).unwrap().unwrap();
+        assert_eq!(message.pgn(), Pgn::new(65226).unwrap());
+        assert_eq!(message.source_address(), Address::new(0x00));
+        assert_eq!(message.destination_address(), Address::GLOBAL);
+        assert_eq!(message.data(), &DM1);
+
+        assert_eq!(receiver.source_address(), None);
+    }
+
+    #[test]
+    fn send() {
+        let mut sender =
+            BamSender::new(Pgn::new(65226).unwrap(), &DM1, 0x00.into())
+                .unwrap()
+                .with_interval(Duration::from_millis(10));
+        let mut receiver = BamReceiver::<32>::new();
+
+        let bam = sender.poll(at(0)).unwrap();
+        assert_eq!(bam.id().as_raw(), 0x1CEC_FF00);
+        assert_eq!(bam.data(), &[0x20, 10, 0, 2, 0xFF, 0xCA, 0xFE, 0x00]);
+
+        // interval is clamped to the minimum
+        assert_eq!(sender.poll_at(), Some(at(50)));
+        assert_eq!(sender.poll(at(49)), None);
+
+        let dt1 = sender.poll(at(50)).unwrap();
+        assert_eq!(dt1.id().as_raw(), 0x1CEB_FF00);
+        assert_eq!(dt1.data(), &[1, 0x40, 0xFF, 0x9D, 0x00, 0x03, 0x01, 0x9E]);
+
+        let dt2 = sender.poll(at(120)).unwrap();
+        assert_eq!(dt2.data(), &[2, 0x00, 0x03, 0x01, 0xFF, 0xFF, 0xFF, 0xFF]);
+
+        assert!(sender.is_complete());
+        assert_eq!(sender.poll_at(), None);
+        assert_eq!(sender.poll(at(1000)), None);
+
+        for (millis, packet) in [(0, bam), (50, dt1)] {
+            let frame: TestFrame = packet.to_frame().unwrap();
+            let view = J1939Frame::new(&frame).unwrap();
+            assert_eq!(receiver.receive(at(millis), &view), Ok(None));
+        }
+
+        let frame: TestFrame = dt2.to_frame().unwrap();
+        let view = J1939Frame::new(&frame).unwrap();
+        let message = receiver.receive(at(120), &view).unwrap().unwrap();
+        assert_eq!(message.data(), &DM1);
+
+        assert_eq!(
+            BamSender::new(Pgn::new(65226).unwrap(), &DM1[..8], 0x00.into())
+                .err(),
+            Some(TransportError::InvalidMessage)
+        );
+    }
+
+    #[test]
+    fn send_max_len() {
+        let mut data = [0; MAX_LEN];
+        for (index, byte) in data.iter_mut().enumerate() {
+            *byte = index as u8;
+        }
+
+        let mut sender =
+            BamSender::new(Pgn::new(65226).unwrap(), &data, 0x00.into())
+                .unwrap();
+        let mut receiver = BamReceiver::<MAX_LEN>::new();
+
+        let mut millis = 0;
+        let mut packets = 0;
+        let mut message = None;
+        while !sender.is_complete() {
+            let packet = sender.poll(at(millis)).unwrap();
+            let frame: TestFrame = packet.to_frame().unwrap();
+            let view = J1939Frame::new(&frame).unwrap();
+            if let Some(reassembled) =
+                receiver.receive(at(millis), &view).unwrap()
+            {
+                let mut received = [0; MAX_LEN];
+                received.copy_from_slice(reassembled.data());
+                message = Some(received);
+            }
+
+            packets += 1;
+            millis += 50;
+        }
+
+        assert_eq!(packets, 256);
+        assert_eq!(message, Some(data));
+        assert_eq!(sender.poll(at(millis)), None);
+    }
+
+    #[test]
+    fn bad_sequence() {
+        let mut receiver = BamReceiver::<32>::new();
+
+        let bam = frame(0x1CEC_FF00, &[0x20, 10, 0, 2, 0xFF, 0xCA, 0xFE, 0x00]);
+        let dt2 =
+            frame(0x1CEB_FF00, &[2, 0x00, 0x03, 0x01, 0xFF, 0xFF, 0xFF, 0xFF]);
+
+        let view = J1939Frame::new(&bam).unwrap();
+        receiver.receive(at(0), &view).unwrap();
+
+        let view = J1939Frame::new(&dt2).unwrap();
+        assert_eq!(
+            receiver.receive(at(50), &view),
+            Err(TransportError::BadSequence)
+        );
+        assert_eq!(receiver.source_address(), None);
+    }
+
+    #[test]
+    fn timeout() {
+        let mut receiver = BamReceiver::<32>::new();
+
+        let bam = frame(0x1CEC_FF00, &[0x20, 10, 0, 2, 0xFF, 0xCA, 0xFE, 0x00]);
+        let dt1 =
+            frame(0x1CEB_FF00, &[1, 0x40, 0xFF, 0x9D, 0x00, 0x03, 0x01, 0x9E]);
+
+        let view = J1939Frame::new(&bam).unwrap();
+        receiver.receive(at(0), &view).unwrap();
+        assert_eq!(receiver.poll(at(750)), Ok(()));
+
+        let view = J1939Frame::new(&dt1).unwrap();
+        assert_eq!(
+            receiver.receive(at(751), &view),
+            Err(TransportError::Timeout)
+        );
+
+        let view = J1939Frame::new(&bam).unwrap();
+        receiver.receive(at(1000), &view).unwrap();
+        assert_eq!(receiver.poll(at(1751)), Err(TransportError::Timeout));
+        assert_eq!(receiver.source_address(), None);
+    }
+
+    #[test]
+    fn rejected() {
+        let mut receiver = BamReceiver::<8>::new();
+
+        // larger than the buffer
+        let bam = frame(0x1CEC_FF00, &[0x20, 10, 0, 2, 0xFF, 0xCA, 0xFE, 0x00]);
+        let view = J1939Frame::new(&bam).unwrap();
+        assert_eq!(
+            receiver.receive(at(0), &view),
+            Err(TransportError::TooLarge)
+        );
+
+        // packet count does not match the size
+        let mut receiver = BamReceiver::<32>::new();
+        let bam = frame(0x1CEC_FF00, &[0x20, 10, 0, 3, 0xFF, 0xCA, 0xFE, 0x00]);
+        let view = J1939Frame::new(&bam).unwrap();
+        assert_eq!(
+            receiver.receive(at(0), &view),
+            Err(TransportError::InvalidMessage)
+        );
+
+        // only one broadcast at a time
+        let bam = frame(0x1CEC_FF00, &[0x20, 10, 0, 2, 0xFF, 0xCA, 0xFE, 0x00]);
+        let view = J1939Frame::new(&bam).unwrap();
+        receiver.receive(at(0), &view).unwrap();
+
+        let bam = frame(0x1CEC_FF01, &[0x20, 10, 0, 2, 0xFF, 0xCA, 0xFE, 0x00]);
+        let view = J1939Frame::new(&bam).unwrap();
+        assert_eq!(receiver.receive(at(0), &view), Err(TransportError::Busy));
+    }
+}
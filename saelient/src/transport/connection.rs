@@ -0,0 +1,956 @@
+//! Connection mode data transfer.
+//!
+//! Both ends of a connection are poll driven. Received frames are passed in
+//! with `receive` and `poll` returns the frames to send. `poll` should be
+//! called often enough to answer the other node within [`TR`](super::TR),
+//! and at least until it returns `None`.
+
+use super::{
+    packets, AbortReason, ConnectionManagement, DataTransfer, Packet,
+    Reassembled, Status, CONNECTION_MANAGEMENT, DATA_TRANSFER, MAX_LEN,
+    PACKET_LEN, T1, T2, T3, T4, TH,
+};
+use crate::error::TransportError;
+use crate::frame::J1939Frame;
+use crate::identifier::{Address, Pgn};
+use crate::time::Instant;
+use embedded_can::Frame;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SenderState {
+    Start,
+    WaitCts { deadline: Instant },
+    Sending { next: u8, last: u8 },
+    WaitAck { deadline: Instant },
+    Done(Status),
+}
+
+/// Sends a message to a single node.
+#[derive(Debug, Clone)]
+pub struct ConnectionSender<'a> {
+    pgn: Pgn,
+    data: &'a [u8],
+    destination_address: Address,
+    source_address: Address,
+    max_packets: Option<u8>,
+    state: SenderState,
+    abort: Option<Packet>,
+}
+
+impl<'a> ConnectionSender<'a> {
+    /// Create a sender for `data`.
+    ///
+    /// `max_packets` limits how many packets the receiver may ask for with
+    /// each clear to send. The request to send goes out on the first `poll`.
+    pub fn new(
+        pgn: Pgn,
+        data: &'a [u8],
+        destination_address: Address,
+        source_address: Address,
+        max_packets: Option<u8>,
+    ) -> Result<Self, TransportError> {
+        if data.len() > MAX_LEN {
+            return Err(TransportError::TooLarge);
+        }
+
+        if data.len() <= PACKET_LEN + 1
+            || !destination_address.is_unicast()
+            || max_packets == Some(0)
+        {
+            return Err(TransportError::InvalidMessage);
+        }
+
+        Ok(Self {
+            pgn,
+            data,
+            destination_address,
+            source_address,
+            max_packets,
+            state: SenderState::Start,
+            abort: None,
+        })
+    }
+
+    /// Progress of the transfer.
+    pub fn status(&self) -> Status {
+        match self.state {
+            SenderState::Done(status) => status,
+            _ => Status::Active,
+        }
+    }
+
+    /// Abort the transfer.
+    pub fn abort(&mut self, reason: AbortReason) {
+        if self.status() == Status::Active {
+            self.abort_with(reason);
+        }
+    }
+
+    /// Next frame to send, if any.
+    pub fn poll(&mut self, now: Instant) -> Option<Packet> {
+        if let Some(packet) = self.abort.take() {
+            return Some(packet);
+        }
+
+        match self.state {
+            SenderState::Start => {
+                self.state = SenderState::WaitCts { deadline: now + T3 };
+
+                Some(self.connection_management(ConnectionManagement::Rts {
+                    size: self.data.len() as u16,
+                    packets: self.packets(),
+                    max_packets: self.max_packets,
+                    pgn: self.pgn,
+                }))
+            }
+            SenderState::Sending { next, last } => {
+                self.state = if next < last {
+                    SenderState::Sending {
+                        next: next + 1,
+                        last,
+                    }
+                } else if last < self.packets() {
+                    SenderState::WaitCts { deadline: now + T3 }
+                } else {
+                    SenderState::WaitAck { deadline: now + T3 }
+                };
+
+                let start = (next as usize - 1) * PACKET_LEN;
+                let end = (start + PACKET_LEN).min(self.data.len());
+
+                Some(Packet::new(
+                    DATA_TRANSFER,
+                    DataTransfer::encode(next, &self.data[start..end]),
+                    self.destination_address,
+                    self.source_address,
+                ))
+            }
+            SenderState::WaitCts { deadline }
+            | SenderState::WaitAck { deadline }
+                if now > deadline =>
+            {
+                self.abort_with(AbortReason::Timeout);
+                self.abort.take()
+            }
+            _ => None,
+        }
+    }
+
+    /// Process a received frame.
+    ///
+    /// Returns [`TransportError::Aborted`] if the receiver aborted the
+    /// connection.
+    pub fn receive<F: Frame>(
+        &mut self,
+        now: Instant,
+        frame: &J1939Frame<'_, F>,
+    ) -> Result<(), TransportError> {
+        if frame.pgn() != CONNECTION_MANAGEMENT
+            || frame.source_address() != self.destination_address
+            || frame.destination_address() != self.source_address
+            || self.status() != Status::Active
+        {
+            return Ok(());
+        }
+
+        let message = ConnectionManagement::decode(frame.data())?;
+
+        match (message, self.state) {
+            (ConnectionManagement::Abort { reason, pgn }, _)
+                if pgn == self.pgn =>
+            {
+                self.state = SenderState::Done(Status::Aborted(reason));
+                Err(TransportError::Aborted)
+            }
+            (ConnectionManagement::Cts { pgn, .. }, _) if pgn != self.pgn => {
+                Ok(())
+            }
+            (ConnectionManagement::Cts { .. }, SenderState::Sending { .. }) => {
+                self.abort_with(AbortReason::CtsWhileSending);
+                Err(TransportError::InvalidMessage)
+            }
+            (
+                ConnectionManagement::Cts { packets: 0, .. },
+                SenderState::WaitCts { .. } | SenderState::WaitAck { .. },
+            ) => {
+                self.state = SenderState::WaitCts { deadline: now + T4 };
+                Ok(())
+            }
+            (
+                ConnectionManagement::Cts { packets, next, .. },
+                SenderState::WaitCts { .. } | SenderState::WaitAck { .. },
+            ) => {
+                let last = next as usize + packets as usize - 1;
+
+                if next == 0 || last > self.packets() as usize {
+                    self.abort_with(AbortReason::Other);
+                    return Err(TransportError::InvalidMessage);
+                }
+
+                self.state = SenderState::Sending {
+                    next,
+                    last: last as u8,
+                };
+                Ok(())
+            }
+            (
+                ConnectionManagement::EndOfMsgAck { pgn, .. },
+                SenderState::WaitAck { .. },
+            ) if pgn == self.pgn => {
+                self.state = SenderState::Done(Status::Complete);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn packets(&self) -> u8 {
+        packets(self.data.len()) as u8
+    }
+
+    fn abort_with(&mut self, reason: AbortReason) {
+        self.state = SenderState::Done(Status::Aborted(reason));
+        self.abort =
+            Some(self.connection_management(ConnectionManagement::Abort {
+                reason,
+                pgn: self.pgn,
+            }));
+    }
+
+    fn connection_management(&self, message: ConnectionManagement) -> Packet {
+        Packet::connection_management(
+            message,
+            self.destination_address,
+            self.source_address,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReceiverState {
+    Receiving { deadline: Instant },
+    Holding { refresh: Instant },
+}
+
+/// Connection being received.
+#[derive(Debug, Clone, Copy)]
+struct Session {
+    pgn: Pgn,
+    source_address: Address,
+    size: usize,
+    packets: u8,
+    max_packets: u8,
+    next: u8,
+    last: u8,
+    hold: bool,
+    state: ReceiverState,
+}
+
+/// Receives messages sent to a single address, one connection at a time.
+///
+/// Messages are reassembled into an internal buffer of `N` bytes, which
+/// defaults to the largest transport protocol message.
+#[derive(Debug, Clone)]
+pub struct ConnectionReceiver<const N: usize = MAX_LEN> {
+    address: Address,
+    window: u8,
+    buffer: [u8; N],
+    session: Option<Session>,
+    response: Option<Packet>,
+    reject: Option<Packet>,
+}
+
+impl<const N: usize> ConnectionReceiver<N> {
+    /// Create a receiver for messages sent to `address`.
+    ///
+    /// `window` is the largest number of packets asked for with each clear
+    /// to send.
+    pub const fn new(address: Address, window: u8) -> Self {
+        Self {
+            address,
+            window: if window == 0 { 1 } else { window },
+            buffer: [0; N],
+            session: None,
+            response: None,
+            reject: None,
+        }
+    }
+
+    /// Address messages are received on.
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Change the address messages are received on.
+    ///
+    /// Any connection in progress is dropped.
+    pub fn set_address(&mut self, address: Address) {
+        self.address = address;
+        self.session = None;
+        self.response = None;
+    }
+
+    /// Source address of the connection being received, if any.
+    pub fn source_address(&self) -> Option<Address> {
+        self.session.map(|session| session.source_address)
+    }
+
+    /// Parameter group of the connection being received, if any.
+    pub fn pgn(&self) -> Option<Pgn> {
+        self.session.map(|session| session.pgn)
+    }
+
+    /// Whether there is no connection in progress and nothing left to send.
+    pub fn is_idle(&self) -> bool {
+        self.session.is_none()
+            && self.response.is_none()
+            && self.reject.is_none()
+    }
+
+    /// Hold the connection open once the current window has been received.
+    ///
+    /// The sender is asked to wait until [`resume`](Self::resume) is called.
+    pub fn hold(&mut self) {
+        if let Some(session) = &mut self.session {
+            session.hold = true;
+        }
+    }
+
+    /// Continue a connection that is being held open.
+    pub fn resume(&mut self, now: Instant) {
+        let Some(session) = &mut self.session else {
+            return;
+        };
+
+        session.hold = false;
+
+        if let ReceiverState::Holding { .. } = session.state {
+            self.clear_to_send(now);
+        }
+    }
+
+    /// Abort the connection in progress.
+    pub fn abort(&mut self, reason: AbortReason) {
+        if let Some(session) = self.session.take() {
+            self.response = Some(self.abort_packet(
+                reason,
+                session.pgn,
+                session.source_address,
+            ));
+        }
+    }
+
+    /// Next frame to send, if any.
+    pub fn poll(&mut self, now: Instant) -> Option<Packet> {
+        if let Some(packet) = self.reject.take() {
+            return Some(packet);
+        }
+
+        if let Some(packet) = self.response.take() {
+            return Some(packet);
+        }
+
+        let session = self.session.as_mut()?;
+
+        match session.state {
+            ReceiverState::Receiving { deadline } if now > deadline => {
+                self.abort(AbortReason::Timeout);
+                self.response.take()
+            }
+            ReceiverState::Holding { refresh } if now >= refresh => {
+                session.state = ReceiverState::Holding { refresh: now + TH };
+                let message = ConnectionManagement::Cts {
+                    packets: 0,
+                    next: session.next,
+                    pgn: session.pgn,
+                };
+
+                Some(Packet::connection_management(
+                    message,
+                    session.source_address,
+                    self.address,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Process a received frame.
+    ///
+    /// Returns the message once its last packet has been received. Errors
+    /// are returned when a connection is refused or aborted, any abort that
+    /// needs to be sent is returned by the next `poll`.
+    pub fn receive<F: Frame>(
+        &mut self,
+        now: Instant,
+        frame: &J1939Frame<'_, F>,
+    ) -> Result<Option<Reassembled<'_>>, TransportError> {
+        if frame.destination_address() != self.address {
+            return Ok(None);
+        }
+
+        match frame.pgn() {
+            CONNECTION_MANAGEMENT => {
+                let message = ConnectionManagement::decode(frame.data())?;
+                self.connection_management(
+                    now,
+                    frame.source_address(),
+                    message,
+                )?;
+                Ok(None)
+            }
+            DATA_TRANSFER => self.data_transfer(now, frame),
+            _ => Ok(None),
+        }
+    }
+
+    fn connection_management(
+        &mut self,
+        now: Instant,
+        source_address: Address,
+        message: ConnectionManagement,
+    ) -> Result<(), TransportError> {
+        match message {
+            ConnectionManagement::Rts {
+                size,
+                packets: packet_count,
+                max_packets,
+                pgn,
+            } => {
+                let size = size as usize;
+
+                match self.session {
+                    Some(session)
+                        if session.source_address != source_address =>
+                    {
+                        self.reject = Some(self.abort_packet(
+                            AbortReason::AlreadyInSession,
+                            pgn,
+                            source_address,
+                        ));
+                        return Err(TransportError::Busy);
+                    }
+                    // a new request from the same node replaces the old one
+                    _ => self.session = None,
+                }
+
+                if size <= PACKET_LEN + 1
+                    || size > MAX_LEN
+                    || packets(size) != packet_count as usize
+                {
+                    self.reject = Some(self.abort_packet(
+                        AbortReason::Other,
+                        pgn,
+                        source_address,
+                    ));
+                    return Err(TransportError::InvalidMessage);
+                }
+
+                if size > N {
+                    self.reject = Some(self.abort_packet(
+                        AbortReason::ResourcesNeeded,
+                        pgn,
+                        source_address,
+                    ));
+                    return Err(TransportError::TooLarge);
+                }
+
+                self.session = Some(Session {
+                    pgn,
+                    source_address,
+                    size,
+                    packets: packet_count,
+                    max_packets: max_packets.unwrap_or(u8::MAX).max(1),
+                    next: 1,
+                    last: 0,
+                    hold: false,
+                    state: ReceiverState::Receiving { deadline: now + T2 },
+                });
+                self.clear_to_send(now);
+
+                Ok(())
+            }
+            ConnectionManagement::Abort { pgn, .. } => match self.session {
+                Some(session)
+                    if session.source_address == source_address
+                        && session.pgn == pgn =>
+                {
+                    self.session = None;
+                    self.response = None;
+                    Err(TransportError::Aborted)
+                }
+                _ => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn data_transfer<F: Frame>(
+        &mut self,
+        now: Instant,
+        frame: &J1939Frame<'_, F>,
+    ) -> Result<Option<Reassembled<'_>>, TransportError> {
+        let Some(session) = self.session else {
+            return Ok(None);
+        };
+
+        if session.source_address != frame.source_address() {
+            return Ok(None);
+        }
+
+        let packet = DataTransfer::new(frame.data())?;
+
+        if let ReceiverState::Holding { .. } = session.state {
+            self.abort(AbortReason::UnexpectedDataTransfer);
+            return Err(TransportError::InvalidMessage);
+        }
+
+        if packet.sequence() != session.next {
+            let reason = if packet.sequence() < session.next {
+                AbortReason::DuplicateSequence
+            } else {
+                AbortReason::BadSequence
+            };
+            self.abort(reason);
+            return Err(TransportError::BadSequence);
+        }
+
+        let start = (packet.sequence() as usize - 1) * PACKET_LEN;
+        let end = (start + PACKET_LEN).min(session.size);
+        self.buffer[start..end].copy_from_slice(&packet.data()[..end - start]);
+
+        if packet.sequence() == session.packets {
+            self.session = None;
+            self.response = Some(Packet::connection_management(
+                ConnectionManagement::EndOfMsgAck {
+                    size: session.size as u16,
+                    packets: session.packets,
+                    pgn: session.pgn,
+                },
+                session.source_address,
+                self.address,
+            ));
+
+            return Ok(Some(Reassembled {
+                pgn: session.pgn,
+                source_address: session.source_address,
+                destination_address: self.address,
+                data: &self.buffer[..session.size],
+            }));
+        }
+
+        // sequence is below the total number of packets, so can't overflow
+        let next = session.next + 1;
+        let window_done = packet.sequence() == session.last;
+
+        if let Some(session) = &mut self.session {
+            session.next = next;
+            session.state = ReceiverState::Receiving { deadline: now + T1 };
+        }
+
+        if window_done {
+            self.clear_to_send(now);
+        }
+
+        Ok(None)
+    }
+
+    /// Queue the clear to send for the next window, or hold the connection.
+    fn clear_to_send(&mut self, now: Instant) {
+        let Some(session) = &mut self.session else {
+            return;
+        };
+
+        let (packets, state) = if session.hold {
+            (0, ReceiverState::Holding { refresh: now + TH })
+        } else {
+            let remaining = session.packets - session.next + 1;
+            let packets = self.window.min(session.max_packets).min(remaining);
+            session.last = session.next + packets - 1;
+            (packets, ReceiverState::Receiving { deadline: now + T2 })
+        };
+
+        session.state = state;
+        self.response = Some(Packet::connection_management(
+            ConnectionManagement::Cts {
+                packets,
+                next: session.next,
+                pgn: session.pgn,
+            },
+            session.source_address,
+            self.address,
+        ));
+    }
+
+    fn abort_packet(
+        &self,
+        reason: AbortReason,
+        pgn: Pgn,
+        destination_address: Address,
+    ) -> Packet {
+        Packet::connection_management(
+            ConnectionManagement::Abort { reason, pgn },
+            destination_address,
+            self.address,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{at, control, packet_frame};
+
+    const PGN: Pgn = Pgn::from_parts(false, false, 0xD8, 0);
+    const SENDER: Address = Address::new(0xF9);
+    const RECEIVER: Address = Address::new(0x00);
+
+    #[test]
+    fn transfer() {
+        let data: [u8; 23] = core::array::from_fn(|i| i as u8);
+        let mut sender =
+            ConnectionSender::new(PGN, &data, RECEIVER, SENDER, None).unwrap();
+        let mut receiver = ConnectionReceiver::<64>::new(RECEIVER, 2);
+
+        let rts = sender.poll(at(0)).unwrap();
+        assert_eq!(rts.id().as_raw(), 0x1CEC_00F9);
+        assert_eq!(
+            control(&rts),
+            ConnectionManagement::Rts {
+                size: 23,
+                packets: 4,
+                max_packets: None,
+                pgn: PGN,
+            }
+        );
+        assert_eq!(sender.poll(at(0)), None);
+
+        let rts = packet_frame(rts);
+        receiver
+            .receive(at(0), &J1939Frame::new(&rts).unwrap())
+            .unwrap();
+
+        let mut received = None;
+        let mut now = 0;
+
+        while sender.status() == Status::Active {
+            now += 10;
+
+            while let Some(packet) = receiver.poll(at(now)) {
+                let packet = packet_frame(packet);
+                let view = J1939Frame::new(&packet).unwrap();
+                sender.receive(at(now), &view).unwrap();
+            }
+
+            while let Some(packet) = sender.poll(at(now)) {
+                let packet = packet_frame(packet);
+                let view = J1939Frame::new(&packet).unwrap();
+                if let Some(message) = receiver.receive(at(now), &view).unwrap()
+                {
+                    let mut copy = [0; 23];
+                    copy.copy_from_slice(message.data());
+                    received =
+                        Some((message.pgn(), message.source_address(), copy));
+                }
+            }
+
+            assert!(now < 1000);
+        }
+
+        assert_eq!(sender.status(), Status::Complete);
+        assert_eq!(received, Some((PGN, SENDER, data)));
+        assert_eq!(receiver.source_address(), None);
+    }
+
+    #[test]
+    fn cts_window() {
+        let data = [0xAA; 23];
+        let mut sender =
+            ConnectionSender::new(PGN, &data, RECEIVER, SENDER, Some(2))
+                .unwrap();
+        sender.poll(at(0)).unwrap();
+
+        let cts = Packet::connection_management(
+            ConnectionManagement::Cts {
+                packets: 2,
+                next: 3,
+                pgn: PGN,
+            },
+            SENDER,
+            RECEIVER,
+        );
+        let cts = packet_frame(cts);
+        sender
+            .receive(at(10), &J1939Frame::new(&cts).unwrap())
+            .unwrap();
+
+        let dt = sender.poll(at(10)).unwrap();
+        assert_eq!(dt.id().as_raw(), 0x1CEB_00F9);
+        assert_eq!(dt.data(), &[3, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]);
+
+        let dt = sender.poll(at(10)).unwrap();
+        assert_eq!(dt.data(), &[4, 0xAA, 0xAA, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+
+        assert_eq!(sender.poll(at(10)), None);
+
+        // CTS while waiting for the end of message acknowledgement asks for
+        // a retransmission
+        let cts = Packet::connection_management(
+            ConnectionManagement::Cts {
+                packets: 1,
+                next: 1,
+                pgn: PGN,
+            },
+            SENDER,
+            RECEIVER,
+        );
+        let cts = packet_frame(cts);
+        sender
+            .receive(at(20), &J1939Frame::new(&cts).unwrap())
+            .unwrap();
+        assert_eq!(sender.poll(at(20)).unwrap().data()[0], 1);
+    }
+
+    #[test]
+    fn sender_timeouts() {
+        let data = [0; 16];
+        let mut sender =
+            ConnectionSender::new(PGN, &data, RECEIVER, SENDER, None).unwrap();
+        sender.poll(at(0)).unwrap();
+
+        assert_eq!(sender.poll(at(1250)), None);
+
+        let abort = sender.poll(at(1251)).unwrap();
+        assert_eq!(
+            control(&abort),
+            ConnectionManagement::Abort {
+                reason: AbortReason::Timeout,
+                pgn: PGN,
+            }
+        );
+        assert_eq!(sender.status(), Status::Aborted(AbortReason::Timeout));
+
+        // hold extends the wait to T4
+        let mut sender =
+            ConnectionSender::new(PGN, &data, RECEIVER, SENDER, None).unwrap();
+        sender.poll(at(0)).unwrap();
+
+        let hold = Packet::connection_management(
+            ConnectionManagement::Cts {
+                packets: 0,
+                next: 1,
+                pgn: PGN,
+            },
+            SENDER,
+            RECEIVER,
+        );
+        let hold = packet_frame(hold);
+        sender
+            .receive(at(100), &J1939Frame::new(&hold).unwrap())
+            .unwrap();
+        assert_eq!(sender.poll(at(1150)), None);
+        assert!(sender.poll(at(1151)).is_some());
+    }
+
+    #[test]
+    fn sender_aborted() {
+        let data = [0; 16];
+        let mut sender =
+            ConnectionSender::new(PGN, &data, RECEIVER, SENDER, None).unwrap();
+        sender.poll(at(0)).unwrap();
+
+        let abort = Packet::connection_management(
+            ConnectionManagement::Abort {
+                reason: AbortReason::ResourcesNeeded,
+                pgn: PGN,
+            },
+            SENDER,
+            RECEIVER,
+        );
+        let abort = packet_frame(abort);
+        assert_eq!(
+            sender.receive(at(10), &J1939Frame::new(&abort).unwrap()),
+            Err(TransportError::Aborted)
+        );
+        assert_eq!(
+            sender.status(),
+            Status::Aborted(AbortReason::ResourcesNeeded)
+        );
+        assert_eq!(sender.poll(at(10)), None);
+
+        assert_eq!(
+            ConnectionSender::new(PGN, &data[..8], RECEIVER, SENDER, None)
+                .err(),
+            Some(TransportError::InvalidMessage)
+        );
+        assert_eq!(
+            ConnectionSender::new(PGN, &data, Address::GLOBAL, SENDER, None)
+                .err(),
+            Some(TransportError::InvalidMessage)
+        );
+    }
+
+    #[test]
+    fn receiver_rejects() {
+        let mut receiver = ConnectionReceiver::<16>::new(RECEIVER, 4);
+
+        let rts = |size: u16, source: Address| {
+            packet_frame(Packet::connection_management(
+                ConnectionManagement::Rts {
+                    size,
+                    packets: packets(size as usize) as u8,
+                    max_packets: None,
+                    pgn: PGN,
+                },
+                RECEIVER,
+                source,
+            ))
+        };
+
+        // too large for the buffer
+        let large = rts(23, SENDER);
+        assert_eq!(
+            receiver.receive(at(0), &J1939Frame::new(&large).unwrap()),
+            Err(TransportError::TooLarge)
+        );
+        assert_eq!(
+            control(&receiver.poll(at(0)).unwrap()),
+            ConnectionManagement::Abort {
+                reason: AbortReason::ResourcesNeeded,
+                pgn: PGN,
+            }
+        );
+
+        // second sender while busy
+        let first = rts(16, SENDER);
+        receiver
+            .receive(at(0), &J1939Frame::new(&first).unwrap())
+            .unwrap();
+        receiver.poll(at(0)).unwrap();
+
+        let second = rts(16, Address::new(0x17));
+        assert_eq!(
+            receiver.receive(at(0), &J1939Frame::new(&second).unwrap()),
+            Err(TransportError::Busy)
+        );
+        let abort = receiver.poll(at(0)).unwrap();
+        assert_eq!(abort.id().as_raw(), 0x1CEC_1700);
+        assert_eq!(
+            control(&abort),
+            ConnectionManagement::Abort {
+                reason: AbortReason::AlreadyInSession,
+                pgn: PGN,
+            }
+        );
+        assert_eq!(receiver.source_address(), Some(SENDER));
+    }
+
+    #[test]
+    fn receiver_sequence_and_timeout() {
+        let mut receiver = ConnectionReceiver::<16>::new(RECEIVER, 4);
+        let rts = packet_frame(Packet::connection_management(
+            ConnectionManagement::Rts {
+                size: 16,
+                packets: 3,
+                max_packets: None,
+                pgn: PGN,
+            },
+            RECEIVER,
+            SENDER,
+        ));
+
+        receiver
+            .receive(at(0), &J1939Frame::new(&rts).unwrap())
+            .unwrap();
+        assert_eq!(
+            control(&receiver.poll(at(0)).unwrap()),
+            ConnectionManagement::Cts {
+                packets: 3,
+                next: 1,
+                pgn: PGN,
+            }
+        );
+
+        let dt = packet_frame(Packet::new(
+            DATA_TRANSFER,
+            DataTransfer::encode(2, &[0; 7]),
+            RECEIVER,
+            SENDER,
+        ));
+        assert_eq!(
+            receiver.receive(at(10), &J1939Frame::new(&dt).unwrap()),
+            Err(TransportError::BadSequence)
+        );
+        assert_eq!(
+            control(&receiver.poll(at(10)).unwrap()),
+            ConnectionManagement::Abort {
+                reason: AbortReason::BadSequence,
+                pgn: PGN,
+            }
+        );
+
+        // no data after the clear to send
+        receiver
+            .receive(at(100), &J1939Frame::new(&rts).unwrap())
+            .unwrap();
+        receiver.poll(at(100)).unwrap();
+        assert_eq!(receiver.poll(at(1350)), None);
+        assert_eq!(
+            control(&receiver.poll(at(1351)).unwrap()),
+            ConnectionManagement::Abort {
+                reason: AbortReason::Timeout,
+                pgn: PGN,
+            }
+        );
+    }
+
+    #[test]
+    fn receiver_hold() {
+        let mut receiver = ConnectionReceiver::<16>::new(RECEIVER, 1);
+        let rts = packet_frame(Packet::connection_management(
+            ConnectionManagement::Rts {
+                size: 16,
+                packets: 3,
+                max_packets: None,
+                pgn: PGN,
+            },
+            RECEIVER,
+            SENDER,
+        ));
+
+        receiver
+            .receive(at(0), &J1939Frame::new(&rts).unwrap())
+            .unwrap();
+        receiver.poll(at(0)).unwrap();
+        receiver.hold();
+
+        let dt = packet_frame(Packet::new(
+            DATA_TRANSFER,
+            DataTransfer::encode(1, &[0; 7]),
+            RECEIVER,
+            SENDER,
+        ));
+        receiver
+            .receive(at(10), &J1939Frame::new(&dt).unwrap())
+            .unwrap();
+
+        let hold = ConnectionManagement::Cts {
+            packets: 0,
+            next: 2,
+            pgn: PGN,
+        };
+        assert_eq!(control(&receiver.poll(at(10)).unwrap()), hold);
+        assert_eq!(receiver.poll(at(509)), None);
+        assert_eq!(control(&receiver.poll(at(510)).unwrap()), hold);
+
+        receiver.resume(at(600));
+        assert_eq!(
+            control(&receiver.poll(at(600)).unwrap()),
+            ConnectionManagement::Cts {
+                packets: 1,
+                next: 2,
+                pgn: PGN,
+            }
+        );
+    }
+}
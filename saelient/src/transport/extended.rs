@@ -0,0 +1,1170 @@
+//! Extended transport protocol.
+//!
+//! Messages larger than [`MAX_LEN`](super::MAX_LEN) are sent to a single
+//! node using the extended connection management (ETP.CM) and data transfer
+//! (ETP.DT) parameter groups. Packets are numbered from the start of the
+//! message with 24 bits. Each data transfer only carries an 8-bit sequence
+//! number, so every window is preceded by a data packet offset (DPO) message
+//! that the sequence numbers are relative to.
+//!
+//! Messages can be up to 117 MB long, so instead of buffers the data is read
+//! from a [`Source`] and written to a [`Sink`] as it is sent.
+//!
+//! See SAEJ1939-21 5.10.5
+
+use super::{
+    AbortReason, DataTransfer, Packet, Status, PACKET_LEN, T1, T2, T3, T4, TH,
+};
+use crate::error::{MessageError, TransportError};
+use crate::frame::J1939Frame;
+use crate::identifier::{Address, Pgn};
+use crate::time::Instant;
+use embedded_can::Frame;
+
+/// Extended connection management parameter group number (ETP.CM).
+pub const CONNECTION_MANAGEMENT: Pgn = Pgn::from_parts(false, false, 0xC8, 0);
+
+/// Extended data transfer parameter group number (ETP.DT).
+pub const DATA_TRANSFER: Pgn = Pgn::from_parts(false, false, 0xC7, 0);
+
+/// Smallest message sent using the extended transport protocol.
+pub const MIN_LEN: u32 = super::MAX_LEN as u32 + 1;
+
+/// Largest message that can be sent using the extended transport protocol.
+pub const MAX_LEN: u32 = MAX_PACKETS * PACKET_LEN as u32;
+
+/// Largest number of packets in a single message.
+pub const MAX_PACKETS: u32 = 0xFF_FFFF;
+
+/// Number of packets needed to send `size` bytes.
+pub const fn packets(size: u32) -> u32 {
+    size.div_ceil(PACKET_LEN as u32)
+}
+
+/// Provides the data of a message being sent.
+pub trait Source {
+    /// Fill `buf` with the message bytes starting at `offset`.
+    ///
+    /// Returning an error aborts the transfer with that reason.
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), AbortReason>;
+}
+
+/// Stores the data of a message being received.
+pub trait Sink {
+    /// Prepare for a message of `size` bytes.
+    ///
+    /// Returning an error refuses the connection with that reason.
+    fn begin(
+        &mut self,
+        pgn: Pgn,
+        source_address: Address,
+        size: u32,
+    ) -> Result<(), AbortReason>;
+
+    /// Store `data` at `offset` bytes from the start of the message.
+    ///
+    /// Returning an error aborts the transfer with that reason.
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), AbortReason>;
+}
+
+impl Source for &[u8] {
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), AbortReason> {
+        let start = offset as usize;
+        let data = self
+            .get(start..start + buf.len())
+            .ok_or(AbortReason::Other)?;
+        buf.copy_from_slice(data);
+        Ok(())
+    }
+}
+
+impl<T: Source + ?Sized> Source for &mut T {
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), AbortReason> {
+        (**self).read(offset, buf)
+    }
+}
+
+impl<T: Sink + ?Sized> Sink for &mut T {
+    fn begin(
+        &mut self,
+        pgn: Pgn,
+        source_address: Address,
+        size: u32,
+    ) -> Result<(), AbortReason> {
+        (**self).begin(pgn, source_address, size)
+    }
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), AbortReason> {
+        (**self).write(offset, data)
+    }
+}
+
+/// Extended connection management message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionManagement {
+    /// Request to send.
+    Rts {
+        /// Total message size in bytes.
+        size: u32,
+        /// Parameter group being sent.
+        pgn: Pgn,
+    },
+    /// Clear to send.
+    Cts {
+        /// Number of packets that can be sent, zero to hold the connection
+        /// open.
+        packets: u8,
+        /// Number of the next packet to send, starting at 1.
+        next: u32,
+        /// Parameter group being sent.
+        pgn: Pgn,
+    },
+    /// Data packet offset.
+    Dpo {
+        /// Number of packets the offset applies to.
+        packets: u8,
+        /// Offset added to the sequence number of the following packets.
+        offset: u32,
+        /// Parameter group being sent.
+        pgn: Pgn,
+    },
+    /// End of message acknowledgement.
+    EndOfMsgAck {
+        /// Total message size in bytes.
+        size: u32,
+        /// Parameter group that was sent.
+        pgn: Pgn,
+    },
+    /// Connection abort.
+    Abort {
+        /// Why the connection was aborted.
+        reason: AbortReason,
+        /// Parameter group being sent.
+        pgn: Pgn,
+    },
+}
+
+impl ConnectionManagement {
+    const RTS: u8 = 20;
+    const CTS: u8 = 21;
+    const DPO: u8 = 22;
+    const END_OF_MSG_ACK: u8 = 23;
+    const ABORT: u8 = 255;
+
+    /// Decode an extended connection management payload.
+    pub fn decode(data: &[u8]) -> Result<Self, MessageError> {
+        if data.len() != 8 {
+            return Err(MessageError::InvalidLength);
+        }
+
+        let pgn = Pgn::new(u32::from_le_bytes([data[5], data[6], data[7], 0]))
+            .ok_or(MessageError::InvalidPgn)?;
+        let size = u32::from_le_bytes([data[1], data[2], data[3], data[4]]);
+        let number = u32::from_le_bytes([data[2], data[3], data[4], 0]);
+
+        match data[0] {
+            Self::RTS => Ok(Self::Rts { size, pgn }),
+            Self::CTS => Ok(Self::Cts {
+                packets: data[1],
+                next: number,
+                pgn,
+            }),
+            Self::DPO => Ok(Self::Dpo {
+                packets: data[1],
+                offset: number,
+                pgn,
+            }),
+            Self::END_OF_MSG_ACK => Ok(Self::EndOfMsgAck { size, pgn }),
+            Self::ABORT => Ok(Self::Abort {
+                reason: AbortReason::from(data[1]),
+                pgn,
+            }),
+            _ => Err(MessageError::InvalidControlByte),
+        }
+    }
+
+    /// Encode the extended connection management payload.
+    ///
+    /// Packet numbers and offsets are truncated to 24 bits.
+    pub fn encode(&self) -> [u8; 8] {
+        let (control, [a, b, c, d], pgn) = match *self {
+            Self::Rts { size, pgn } => (Self::RTS, size.to_le_bytes(), pgn),
+            Self::Cts { packets, next, pgn } => {
+                let next = next.to_le_bytes();
+                (Self::CTS, [packets, next[0], next[1], next[2]], pgn)
+            }
+            Self::Dpo {
+                packets,
+                offset,
+                pgn,
+            } => {
+                let offset = offset.to_le_bytes();
+                (Self::DPO, [packets, offset[0], offset[1], offset[2]], pgn)
+            }
+            Self::EndOfMsgAck { size, pgn } => {
+                (Self::END_OF_MSG_ACK, size.to_le_bytes(), pgn)
+            }
+            Self::Abort { reason, pgn } => {
+                (Self::ABORT, [reason.into(), 0xFF, 0xFF, 0xFF], pgn)
+            }
+        };
+        let pgn = pgn.as_raw().to_le_bytes();
+
+        [control, a, b, c, d, pgn[0], pgn[1], pgn[2]]
+    }
+}
+
+impl Packet {
+    fn extended(
+        message: ConnectionManagement,
+        destination_address: Address,
+        source_address: Address,
+    ) -> Self {
+        Self::new(
+            CONNECTION_MANAGEMENT,
+            message.encode(),
+            destination_address,
+            source_address,
+        )
+    }
+}
+
+/// Message received with the extended transport protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Received {
+    pgn: Pgn,
+    source_address: Address,
+    destination_address: Address,
+    size: u32,
+}
+
+impl Received {
+    /// Parameter group of the message.
+    pub fn pgn(&self) -> Pgn {
+        self.pgn
+    }
+
+    /// Node that sent the message.
+    pub fn source_address(&self) -> Address {
+        self.source_address
+    }
+
+    /// Node the message was sent to.
+    pub fn destination_address(&self) -> Address {
+        self.destination_address
+    }
+
+    /// Message size in bytes, all of which have been written to the sink.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SenderState {
+    Start,
+    WaitCts { deadline: Instant },
+    Offset { next: u32, packets: u8 },
+    Sending { offset: u32, sequence: u8, last: u8 },
+    WaitAck { deadline: Instant },
+    Done(Status),
+}
+
+/// Sends a message read from a [`Source`] to a single node.
+#[derive(Debug, Clone)]
+pub struct ExtendedSender<S> {
+    pgn: Pgn,
+    size: u32,
+    source: S,
+    destination_address: Address,
+    source_address: Address,
+    state: SenderState,
+    abort: Option<Packet>,
+}
+
+impl<S: Source> ExtendedSender<S> {
+    /// Create a sender for `size` bytes read from `source`.
+    ///
+    /// The request to send goes out on the first `poll`.
+    pub fn new(
+        pgn: Pgn,
+        size: u32,
+        source: S,
+        destination_address: Address,
+        source_address: Address,
+    ) -> Result<Self, TransportError> {
+        if size > MAX_LEN {
+            return Err(TransportError::TooLarge);
+        }
+
+        if size < MIN_LEN || !destination_address.is_unicast() {
+            return Err(TransportError::InvalidMessage);
+        }
+
+        Ok(Self {
+            pgn,
+            size,
+            source,
+            destination_address,
+            source_address,
+            state: SenderState::Start,
+            abort: None,
+        })
+    }
+
+    /// Progress of the transfer.
+    pub fn status(&self) -> Status {
+        match self.state {
+            SenderState::Done(status) => status,
+            _ => Status::Active,
+        }
+    }
+
+    /// Abort the transfer.
+    pub fn abort(&mut self, reason: AbortReason) {
+        if self.status() == Status::Active {
+            self.abort_with(reason);
+        }
+    }
+
+    /// Return the source.
+    pub fn into_source(self) -> S {
+        self.source
+    }
+
+    /// Next frame to send, if any.
+    pub fn poll(&mut self, now: Instant) -> Option<Packet> {
+        if let Some(packet) = self.abort.take() {
+            return Some(packet);
+        }
+
+        match self.state {
+            SenderState::Start => {
+                self.state = SenderState::WaitCts { deadline: now + T3 };
+
+                Some(self.connection_management(ConnectionManagement::Rts {
+                    size: self.size,
+                    pgn: self.pgn,
+                }))
+            }
+            SenderState::Offset { next, packets } => {
+                let offset = next - 1;
+                self.state = SenderState::Sending {
+                    offset,
+                    sequence: 1,
+                    last: packets,
+                };
+
+                Some(self.connection_management(ConnectionManagement::Dpo {
+                    packets,
+                    offset,
+                    pgn: self.pgn,
+                }))
+            }
+            SenderState::Sending {
+                offset,
+                sequence,
+                last,
+            } => {
+                let start = (offset + sequence as u32 - 1) * PACKET_LEN as u32;
+                let len = (self.size - start).min(PACKET_LEN as u32) as usize;
+                let mut data = [0; PACKET_LEN];
+
+                if let Err(reason) = self.source.read(start, &mut data[..len]) {
+                    self.abort_with(reason);
+                    return self.abort.take();
+                }
+
+                self.state = if sequence < last {
+                    SenderState::Sending {
+                        offset,
+                        sequence: sequence + 1,
+                        last,
+                    }
+                } else if offset + (last as u32) < packets(self.size) {
+                    SenderState::WaitCts { deadline: now + T3 }
+                } else {
+                    SenderState::WaitAck { deadline: now + T3 }
+                };
+
+                Some(Packet::new(
+                    DATA_TRANSFER,
+                    DataTransfer::encode(sequence, &data[..len]),
+                    self.destination_address,
+                    self.source_address,
+                ))
+            }
+            SenderState::WaitCts { deadline }
+            | SenderState::WaitAck { deadline }
+                if now > deadline =>
+            {
+                self.abort_with(AbortReason::Timeout);
+                self.abort.take()
+            }
+            _ => None,
+        }
+    }
+
+    /// Process a received frame.
+    ///
+    /// Returns [`TransportError::Aborted`] if the receiver aborted the
+    /// connection.
+    pub fn receive<F: Frame>(
+        &mut self,
+        now: Instant,
+        frame: &J1939Frame<'_, F>,
+    ) -> Result<(), TransportError> {
+        if frame.pgn() != CONNECTION_MANAGEMENT
+            || frame.source_address() != self.destination_address
+            || frame.destination_address() != self.source_address
+            || self.status() != Status::Active
+        {
+            return Ok(());
+        }
+
+        let message = ConnectionManagement::decode(frame.data())?;
+
+        match (message, self.state) {
+            (ConnectionManagement::Abort { reason, pgn }, _)
+                if pgn == self.pgn =>
+            {
+                self.state = SenderState::Done(Status::Aborted(reason));
+                Err(TransportError::Aborted)
+            }
+            (ConnectionManagement::Cts { pgn, .. }, _) if pgn != self.pgn => {
+                self.abort_with(AbortReason::UnexpectedCtsPgn);
+                Err(TransportError::InvalidMessage)
+            }
+            (
+                ConnectionManagement::Cts { .. },
+                SenderState::Offset { .. } | SenderState::Sending { .. },
+            ) => {
+                self.abort_with(AbortReason::CtsWhileSending);
+                Err(TransportError::InvalidMessage)
+            }
+            (
+                ConnectionManagement::Cts { packets: 0, .. },
+                SenderState::WaitCts { .. } | SenderState::WaitAck { .. },
+            ) => {
+                self.state = SenderState::WaitCts { deadline: now + T4 };
+                Ok(())
+            }
+            (
+                ConnectionManagement::Cts {
+                    packets: count,
+                    next,
+                    ..
+                },
+                SenderState::WaitCts { .. } | SenderState::WaitAck { .. },
+            ) => {
+                if next == 0 || next - 1 + count as u32 > packets(self.size) {
+                    self.abort_with(AbortReason::CtsTooLarge);
+                    return Err(TransportError::InvalidMessage);
+                }
+
+                self.state = SenderState::Offset {
+                    next,
+                    packets: count,
+                };
+                Ok(())
+            }
+            (
+                ConnectionManagement::EndOfMsgAck { pgn, .. },
+                SenderState::WaitAck { .. },
+            ) if pgn == self.pgn => {
+                self.state = SenderState::Done(Status::Complete);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn abort_with(&mut self, reason: AbortReason) {
+        self.state = SenderState::Done(Status::Aborted(reason));
+        self.abort =
+            Some(self.connection_management(ConnectionManagement::Abort {
+                reason,
+                pgn: self.pgn,
+            }));
+    }
+
+    fn connection_management(&self, message: ConnectionManagement) -> Packet {
+        Packet::extended(message, self.destination_address, self.source_address)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReceiverState {
+    Receiving { deadline: Instant },
+    Holding { refresh: Instant },
+}
+
+/// Connection being received.
+#[derive(Debug, Clone, Copy)]
+struct Session {
+    pgn: Pgn,
+    source_address: Address,
+    size: u32,
+    /// Number of the next packet, starting at 1.
+    next: u32,
+    /// Number of the last packet in the current window.
+    last: u32,
+    /// Data packet offset of the current window, once received.
+    offset: Option<u32>,
+    hold: bool,
+    state: ReceiverState,
+}
+
+impl Session {
+    fn packets(&self) -> u32 {
+        packets(self.size)
+    }
+}
+
+/// Receives messages sent to a single address into a [`Sink`], one
+/// connection at a time.
+#[derive(Debug, Clone)]
+pub struct ExtendedReceiver<S> {
+    address: Address,
+    window: u8,
+    sink: S,
+    session: Option<Session>,
+    response: Option<Packet>,
+    reject: Option<Packet>,
+}
+
+impl<S: Sink> ExtendedReceiver<S> {
+    /// Create a receiver for messages sent to `address`.
+    ///
+    /// `window` is the largest number of packets asked for with each clear
+    /// to send.
+    pub fn new(address: Address, window: u8, sink: S) -> Self {
+        Self {
+            address,
+            window: window.max(1),
+            sink,
+            session: None,
+            response: None,
+            reject: None,
+        }
+    }
+
+    /// Address messages are received on.
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Source address of the connection being received, if any.
+    pub fn source_address(&self) -> Option<Address> {
+        self.session.map(|session| session.source_address)
+    }
+
+    /// Parameter group of the connection being received, if any.
+    pub fn pgn(&self) -> Option<Pgn> {
+        self.session.map(|session| session.pgn)
+    }
+
+    /// The sink messages are written to.
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    /// The sink messages are written to.
+    pub fn sink_mut(&mut self) -> &mut S {
+        &mut self.sink
+    }
+
+    /// Hold the connection open once the current window has been received.
+    ///
+    /// The sender is asked to wait until [`resume`](Self::resume) is called,
+    /// for example while the sink erases flash.
+    pub fn hold(&mut self) {
+        if let Some(session) = &mut self.session {
+            session.hold = true;
+        }
+    }
+
+    /// Continue a connection that is being held open.
+    pub fn resume(&mut self, now: Instant) {
+        let Some(session) = &mut self.session else {
+            return;
+        };
+
+        session.hold = false;
+
+        if let ReceiverState::Holding { .. } = session.state {
+            self.clear_to_send(now);
+        }
+    }
+
+    /// Abort the connection in progress.
+    pub fn abort(&mut self, reason: AbortReason) {
+        if let Some(session) = self.session.take() {
+            self.response = Some(self.abort_packet(
+                reason,
+                session.pgn,
+                session.source_address,
+            ));
+        }
+    }
+
+    /// Next frame to send, if any.
+    pub fn poll(&mut self, now: Instant) -> Option<Packet> {
+        if let Some(packet) = self.reject.take() {
+            return Some(packet);
+        }
+
+        if let Some(packet) = self.response.take() {
+            return Some(packet);
+        }
+
+        let session = self.session.as_mut()?;
+
+        match session.state {
+            ReceiverState::Receiving { deadline } if now > deadline => {
+                self.abort(AbortReason::Timeout);
+                self.response.take()
+            }
+            ReceiverState::Holding { refresh } if now >= refresh => {
+                session.state = ReceiverState::Holding { refresh: now + TH };
+                let message = ConnectionManagement::Cts {
+                    packets: 0,
+                    next: session.next,
+                    pgn: session.pgn,
+                };
+
+                Some(Packet::extended(
+                    message,
+                    session.source_address,
+                    self.address,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Process a received frame.
+    ///
+    /// Returns the message once its last packet has been written to the
+    /// sink. Errors are returned when a connection is refused or aborted,
+    /// any abort that needs to be sent is returned by the next `poll`.
+    pub fn receive<F: Frame>(
+        &mut self,
+        now: Instant,
+        frame: &J1939Frame<'_, F>,
+    ) -> Result<Option<Received>, TransportError> {
+        if frame.destination_address() != self.address {
+            return Ok(None);
+        }
+
+        match frame.pgn() {
+            CONNECTION_MANAGEMENT => {
+                let message = ConnectionManagement::decode(frame.data())?;
+                self.connection_management(
+                    now,
+                    frame.source_address(),
+                    message,
+                )?;
+                Ok(None)
+            }
+            DATA_TRANSFER => self.data_transfer(now, frame),
+            _ => Ok(None),
+        }
+    }
+
+    fn connection_management(
+        &mut self,
+        now: Instant,
+        source_address: Address,
+        message: ConnectionManagement,
+    ) -> Result<(), TransportError> {
+        match message {
+            ConnectionManagement::Rts { size, pgn } => {
+                match self.session {
+                    Some(session)
+                        if session.source_address != source_address =>
+                    {
+                        self.reject = Some(self.abort_packet(
+                            AbortReason::AlreadyInSession,
+                            pgn,
+                            source_address,
+                        ));
+                        return Err(TransportError::Busy);
+                    }
+                    // a new request from the same node replaces the old one
+                    _ => self.session = None,
+                }
+
+                if !(MIN_LEN..=MAX_LEN).contains(&size) {
+                    self.reject = Some(self.abort_packet(
+                        AbortReason::Other,
+                        pgn,
+                        source_address,
+                    ));
+                    return Err(TransportError::InvalidMessage);
+                }
+
+                if let Err(reason) = self.sink.begin(pgn, source_address, size)
+                {
+                    self.reject =
+                        Some(self.abort_packet(reason, pgn, source_address));
+                    return Err(TransportError::Aborted);
+                }
+
+                self.session = Some(Session {
+                    pgn,
+                    source_address,
+                    size,
+                    next: 1,
+                    last: 0,
+                    offset: None,
+                    hold: false,
+                    state: ReceiverState::Receiving { deadline: now + T2 },
+                });
+                self.clear_to_send(now);
+
+                Ok(())
+            }
+            ConnectionManagement::Dpo {
+                packets,
+                offset,
+                pgn,
+            } => {
+                let Some(session) = &mut self.session else {
+                    return Ok(());
+                };
+
+                if session.source_address != source_address {
+                    return Ok(());
+                }
+
+                let reason = if session.pgn != pgn {
+                    AbortReason::UnexpectedDataPacketOffsetPgn
+                } else if session.offset.is_some()
+                    || matches!(session.state, ReceiverState::Holding { .. })
+                {
+                    AbortReason::UnexpectedDataPacketOffset
+                } else if offset + 1 != session.next {
+                    AbortReason::BadDataPacketOffset
+                } else if packets == 0 || offset + packets as u32 > session.last
+                {
+                    AbortReason::DataPacketOffsetTooLarge
+                } else {
+                    session.offset = Some(offset);
+                    session.last = offset + packets as u32;
+                    session.state =
+                        ReceiverState::Receiving { deadline: now + T1 };
+                    return Ok(());
+                };
+
+                self.abort(reason);
+                Err(TransportError::InvalidMessage)
+            }
+            ConnectionManagement::Abort { pgn, .. } => match self.session {
+                Some(session)
+                    if session.source_address == source_address
+                        && session.pgn == pgn =>
+                {
+                    self.session = None;
+                    self.response = None;
+                    Err(TransportError::Aborted)
+                }
+                _ => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn data_transfer<F: Frame>(
+        &mut self,
+        now: Instant,
+        frame: &J1939Frame<'_, F>,
+    ) -> Result<Option<Received>, TransportError> {
+        let Some(session) = self.session else {
+            return Ok(None);
+        };
+
+        if session.source_address != frame.source_address() {
+            return Ok(None);
+        }
+
+        let packet = DataTransfer::new(frame.data())?;
+
+        let Some(offset) = session.offset else {
+            self.abort(AbortReason::UnexpectedDataTransfer);
+            return Err(TransportError::InvalidMessage);
+        };
+
+        let number = offset + packet.sequence() as u32;
+
+        if number != session.next {
+            let reason = if number < session.next {
+                AbortReason::DuplicateSequence
+            } else {
+                AbortReason::BadSequence
+            };
+            self.abort(reason);
+            return Err(TransportError::BadSequence);
+        }
+
+        let start = (number - 1) * PACKET_LEN as u32;
+        let len = (session.size - start).min(PACKET_LEN as u32) as usize;
+
+        if let Err(reason) = self.sink.write(start, &packet.data()[..len]) {
+            self.abort(reason);
+            return Err(TransportError::Aborted);
+        }
+
+        if number == session.packets() {
+            self.session = None;
+            self.response = Some(Packet::extended(
+                ConnectionManagement::EndOfMsgAck {
+                    size: session.size,
+                    pgn: session.pgn,
+                },
+                session.source_address,
+                self.address,
+            ));
+
+            return Ok(Some(Received {
+                pgn: session.pgn,
+                source_address: session.source_address,
+                destination_address: self.address,
+                size: session.size,
+            }));
+        }
+
+        if let Some(session) = &mut self.session {
+            session.next = number + 1;
+            session.state = ReceiverState::Receiving { deadline: now + T1 };
+        }
+
+        if number == session.last {
+            self.clear_to_send(now);
+        }
+
+        Ok(None)
+    }
+
+    /// Queue the clear to send for the next window, or hold the connection.
+    fn clear_to_send(&mut self, now: Instant) {
+        let Some(session) = &mut self.session else {
+            return;
+        };
+
+        session.offset = None;
+
+        let (packets, state) = if session.hold {
+            (0, ReceiverState::Holding { refresh: now + TH })
+        } else {
+            let remaining = session.packets() - session.next + 1;
+            let packets = remaining.min(self.window as u32) as u8;
+            session.last = session.next + packets as u32 - 1;
+            (packets, ReceiverState::Receiving { deadline: now + T2 })
+        };
+
+        session.state = state;
+        self.response = Some(Packet::extended(
+            ConnectionManagement::Cts {
+                packets,
+                next: session.next,
+                pgn: session.pgn,
+            },
+            session.source_address,
+            self.address,
+        ));
+    }
+
+    fn abort_packet(
+        &self,
+        reason: AbortReason,
+        pgn: Pgn,
+        destination_address: Address,
+    ) -> Packet {
+        Packet::extended(
+            ConnectionManagement::Abort { reason, pgn },
+            destination_address,
+            self.address,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{at, packet_frame};
+
+    const PGN: Pgn = Pgn::from_parts(false, false, 0xD8, 0);
+    const SENDER: Address = Address::new(0xF9);
+    const RECEIVER: Address = Address::new(0x00);
+    const SIZE: u32 = 2000;
+
+    fn control(packet: &Packet) -> ConnectionManagement {
+        ConnectionManagement::decode(packet.data()).unwrap()
+    }
+
+    struct Buffer {
+        data: [u8; SIZE as usize],
+        size: u32,
+    }
+
+    impl Sink for Buffer {
+        fn begin(
+            &mut self,
+            _pgn: Pgn,
+            _source_address: Address,
+            size: u32,
+        ) -> Result<(), AbortReason> {
+            if size > SIZE {
+                return Err(AbortReason::ResourcesNeeded);
+            }
+
+            self.size = size;
+            Ok(())
+        }
+
+        fn write(
+            &mut self,
+            offset: u32,
+            data: &[u8],
+        ) -> Result<(), AbortReason> {
+            let start = offset as usize;
+            self.data[start..start + data.len()].copy_from_slice(data);
+            Ok(())
+        }
+    }
+
+    fn buffer() -> Buffer {
+        Buffer {
+            data: [0; SIZE as usize],
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn codec() {
+        let messages = [
+            (
+                ConnectionManagement::Rts {
+                    size: 100_000,
+                    pgn: PGN,
+                },
+                [20, 0xA0, 0x86, 0x01, 0x00, 0x00, 0xD8, 0x00],
+            ),
+            (
+                ConnectionManagement::Cts {
+                    packets: 16,
+                    next: 0x01_0203,
+                    pgn: PGN,
+                },
+                [21, 16, 0x03, 0x02, 0x01, 0x00, 0xD8, 0x00],
+            ),
+            (
+                ConnectionManagement::Dpo {
+                    packets: 16,
+                    offset: 0x01_0202,
+                    pgn: PGN,
+                },
+                [22, 16, 0x02, 0x02, 0x01, 0x00, 0xD8, 0x00],
+            ),
+            (
+                ConnectionManagement::EndOfMsgAck {
+                    size: 100_000,
+                    pgn: PGN,
+                },
+                [23, 0xA0, 0x86, 0x01, 0x00, 0x00, 0xD8, 0x00],
+            ),
+            (
+                ConnectionManagement::Abort {
+                    reason: AbortReason::BadDataPacketOffset,
+                    pgn: PGN,
+                },
+                [255, 12, 0xFF, 0xFF, 0xFF, 0x00, 0xD8, 0x00],
+            ),
+        ];
+
+        for (message, data) in messages {
+            assert_eq!(message.encode(), data);
+            assert_eq!(ConnectionManagement::decode(&data), Ok(message));
+        }
+
+        assert_eq!(
+            ConnectionManagement::decode(&[16, 0, 0, 0, 0, 0, 0xD8, 0]),
+            Err(MessageError::InvalidControlByte)
+        );
+        assert_eq!(MAX_LEN, 117_440_505);
+    }
+
+    #[test]
+    fn transfer() {
+        let data: [u8; SIZE as usize] = core::array::from_fn(|i| i as u8);
+        let mut sender =
+            ExtendedSender::new(PGN, SIZE, &data[..], RECEIVER, SENDER)
+                .unwrap();
+        let mut receiver = ExtendedReceiver::new(RECEIVER, 255, buffer());
+
+        let mut received = None;
+        let mut offsets = 0;
+        let mut now = 0;
+
+        while sender.status() == Status::Active {
+            now += 10;
+
+            while let Some(packet) = sender.poll(at(now)) {
+                if packet.id().pgn() == CONNECTION_MANAGEMENT {
+                    assert_eq!(packet.id().as_raw(), 0x1CC8_00F9);
+
+                    if let ConnectionManagement::Dpo { offset, .. } =
+                        control(&packet)
+                    {
+                        assert_eq!(offset, offsets * 255);
+                        offsets += 1;
+                    }
+                }
+
+                let packet = packet_frame(packet);
+                let view = J1939Frame::new(&packet).unwrap();
+                if let Some(message) = receiver.receive(at(now), &view).unwrap()
+                {
+                    received = Some(message);
+                }
+            }
+
+            while let Some(packet) = receiver.poll(at(now)) {
+                let packet = packet_frame(packet);
+                let view = J1939Frame::new(&packet).unwrap();
+                sender.receive(at(now), &view).unwrap();
+            }
+
+            assert!(now < 1000);
+        }
+
+        // 286 packets in windows of 255 and 31
+        assert_eq!(offsets, 2);
+        assert_eq!(sender.status(), Status::Complete);
+
+        let received = received.unwrap();
+        assert_eq!(received.pgn(), PGN);
+        assert_eq!(received.source_address(), SENDER);
+        assert_eq!(received.size(), SIZE);
+        assert_eq!(receiver.sink().size, SIZE);
+        assert_eq!(receiver.sink().data, data);
+    }
+
+    #[test]
+    fn refused() {
+        let mut receiver = ExtendedReceiver::new(RECEIVER, 16, buffer());
+
+        let rts = packet_frame(Packet::extended(
+            ConnectionManagement::Rts {
+                size: 100_000,
+                pgn: PGN,
+            },
+            RECEIVER,
+            SENDER,
+        ));
+        assert_eq!(
+            receiver.receive(at(0), &J1939Frame::new(&rts).unwrap()),
+            Err(TransportError::Aborted)
+        );
+        assert_eq!(
+            control(&receiver.poll(at(0)).unwrap()),
+            ConnectionManagement::Abort {
+                reason: AbortReason::ResourcesNeeded,
+                pgn: PGN,
+            }
+        );
+
+        // fits in the transport protocol
+        let rts = packet_frame(Packet::extended(
+            ConnectionManagement::Rts {
+                size: 100,
+                pgn: PGN,
+            },
+            RECEIVER,
+            SENDER,
+        ));
+        assert_eq!(
+            receiver.receive(at(0), &J1939Frame::new(&rts).unwrap()),
+            Err(TransportError::InvalidMessage)
+        );
+
+        assert_eq!(
+            ExtendedSender::new(PGN, 100, &[0u8; 100][..], RECEIVER, SENDER)
+                .err(),
+            Some(TransportError::InvalidMessage)
+        );
+    }
+
+    #[test]
+    fn bad_offset() {
+        let mut receiver = ExtendedReceiver::new(RECEIVER, 16, buffer());
+
+        let rts = packet_frame(Packet::extended(
+            ConnectionManagement::Rts {
+                size: SIZE,
+                pgn: PGN,
+            },
+            RECEIVER,
+            SENDER,
+        ));
+        receiver
+            .receive(at(0), &J1939Frame::new(&rts).unwrap())
+            .unwrap();
+        assert_eq!(
+            control(&receiver.poll(at(0)).unwrap()),
+            ConnectionManagement::Cts {
+                packets: 16,
+                next: 1,
+                pgn: PGN,
+            }
+        );
+
+        // data before the offset
+        let dt = packet_frame(Packet::new(
+            DATA_TRANSFER,
+            DataTransfer::encode(1, &[0; 7]),
+            RECEIVER,
+            SENDER,
+        ));
+        assert_eq!(
+            receiver.receive(at(10), &J1939Frame::new(&dt).unwrap()),
+            Err(TransportError::InvalidMessage)
+        );
+        assert_eq!(
+            control(&receiver.poll(at(10)).unwrap()),
+            ConnectionManagement::Abort {
+                reason: AbortReason::UnexpectedDataTransfer,
+                pgn: PGN,
+            }
+        );
+
+        // offset past the clear to send
+        receiver
+            .receive(at(20), &J1939Frame::new(&rts).unwrap())
+            .unwrap();
+        receiver.poll(at(20)).unwrap();
+
+        let dpo = packet_frame(Packet::extended(
+            ConnectionManagement::Dpo {
+                packets: 16,
+                offset: 16,
+                pgn: PGN,
+            },
+            RECEIVER,
+            SENDER,
+        ));
+        assert_eq!(
+            receiver.receive(at(30), &J1939Frame::new(&dpo).unwrap()),
+            Err(TransportError::InvalidMessage)
+        );
+        assert_eq!(
+            control(&receiver.poll(at(30)).unwrap()),
+            ConnectionManagement::Abort {
+                reason: AbortReason::BadDataPacketOffset,
+                pgn: PGN,
+            }
+        );
+        assert_eq!(receiver.source_address(), None);
+    }
+}
@@ -0,0 +1,433 @@
+//! NMEA 2000 Fast Packet.
+//!
+//! NMEA 2000 uses J1939 addressing but sends most messages longer than 8
+//! bytes as a burst of up to 32 frames without any connection management.
+//! The first frame carries the total length and 6 data bytes, the others 7
+//! data bytes each. Every frame starts with a 3-bit sequence counter, which
+//! changes with each message of the same parameter group, and a 5-bit frame
+//! counter.
+//!
+//! Which parameter groups use Fast Packet is defined by the NMEA 2000
+//! database, so it is up to the caller to only pass those frames in.
+
+use super::{Packet, Reassembled};
+use crate::error::TransportError;
+use crate::frame::J1939Frame;
+use crate::identifier::{Address, J1939Id, Pgn};
+use crate::time::Instant;
+use core::time::Duration;
+use embedded_can::Frame;
+
+/// Largest message that can be sent as a Fast Packet.
+pub const MAX_LEN: usize = FIRST_LEN + 31 * PACKET_LEN;
+
+/// Number of data bytes in the first frame.
+pub const FIRST_LEN: usize = 6;
+
+/// Number of data bytes in each following frame.
+pub const PACKET_LEN: usize = 7;
+
+/// Time allowed between frames of a message.
+pub const TIMEOUT: Duration = Duration::from_millis(750);
+
+/// Number of frames needed to send `size` bytes.
+pub const fn frames(size: usize) -> usize {
+    if size <= FIRST_LEN {
+        1
+    } else {
+        1 + (size - FIRST_LEN).div_ceil(PACKET_LEN)
+    }
+}
+
+/// Sequence counter for one parameter group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SequenceCounter(u8);
+
+impl SequenceCounter {
+    /// Create a counter starting at 0.
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    /// Sequence counter for the next message, wrapping after 7.
+    pub fn advance(&mut self) -> u8 {
+        let sequence = self.0;
+        self.0 = (self.0 + 1) & 0x07;
+        sequence
+    }
+}
+
+/// Splits a message into Fast Packet frames.
+#[derive(Debug, Clone)]
+pub struct FastPacketSender<'a> {
+    id: J1939Id,
+    data: &'a [u8],
+    sequence: u8,
+    frame: u8,
+}
+
+impl<'a> FastPacketSender<'a> {
+    /// Create a sender for `data`, sent with `id`.
+    ///
+    /// Only the low 3 bits of `sequence` are used.
+    pub fn new(
+        id: J1939Id,
+        data: &'a [u8],
+        sequence: u8,
+    ) -> Result<Self, TransportError> {
+        if data.len() > MAX_LEN {
+            return Err(TransportError::TooLarge);
+        }
+
+        Ok(Self {
+            id,
+            data,
+            sequence: sequence & 0x07,
+            frame: 0,
+        })
+    }
+}
+
+impl Iterator for FastPacketSender<'_> {
+    type Item = Packet;
+
+    fn next(&mut self) -> Option<Packet> {
+        if self.frame as usize >= frames(self.data.len()) {
+            return None;
+        }
+
+        let mut data = [0xFF; 8];
+        data[0] = self.sequence << 5 | self.frame;
+
+        if self.frame == 0 {
+            let end = self.data.len().min(FIRST_LEN);
+            data[1] = self.data.len() as u8;
+            data[2..2 + end].copy_from_slice(&self.data[..end]);
+        } else {
+            let start = FIRST_LEN + (self.frame as usize - 1) * PACKET_LEN;
+            let end = (start + PACKET_LEN).min(self.data.len());
+            data[1..1 + end - start].copy_from_slice(&self.data[start..end]);
+        }
+
+        self.frame += 1;
+
+        Some(Packet { id: self.id, data })
+    }
+}
+
+/// Message being received.
+#[derive(Debug, Clone)]
+struct Session {
+    pgn: Pgn,
+    source_address: Address,
+    destination_address: Address,
+    sequence: u8,
+    size: usize,
+    next: u8,
+    received: usize,
+    deadline: Instant,
+    /// Cleared once the message is complete or dropped.
+    open: bool,
+    buffer: [u8; MAX_LEN],
+}
+
+impl Session {
+    fn reassembled(&self) -> Reassembled<'_> {
+        Reassembled {
+            pgn: self.pgn,
+            source_address: self.source_address,
+            destination_address: self.destination_address,
+            data: &self.buffer[..self.size],
+        }
+    }
+}
+
+/// Reassembles up to `S` Fast Packet messages at the same time.
+///
+/// There is one session for each source address and parameter group.
+#[derive(Debug, Clone)]
+pub struct FastPacketReceiver<const S: usize> {
+    sessions: [Option<Session>; S],
+}
+
+impl<const S: usize> Default for FastPacketReceiver<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const S: usize> FastPacketReceiver<S> {
+    /// Create an idle receiver.
+    pub const fn new() -> Self {
+        Self {
+            sessions: [const { None }; S],
+        }
+    }
+
+    /// Drop messages that timed out.
+    ///
+    /// Returns [`TransportError::Timeout`] when any message was dropped.
+    pub fn poll(&mut self, now: Instant) -> Result<(), TransportError> {
+        let mut result = Ok(());
+
+        for session in self.sessions.iter_mut().flatten() {
+            if session.open && now > session.deadline {
+                session.open = false;
+                result = Err(TransportError::Timeout);
+            }
+        }
+
+        result
+    }
+
+    /// Process a received Fast Packet frame.
+    ///
+    /// Returns the message once its last frame has been received. Frames of
+    /// a message whose first frame was missed are ignored.
+    pub fn receive<F: Frame>(
+        &mut self,
+        now: Instant,
+        frame: &J1939Frame<'_, F>,
+    ) -> Result<Option<Reassembled<'_>>, TransportError> {
+        let data = frame.data();
+
+        if data.len() < 2 {
+            return Err(TransportError::InvalidMessage);
+        }
+
+        let sequence = data[0] >> 5;
+        let counter = data[0] & 0x1F;
+        let index = self.find(frame.source_address(), frame.pgn());
+
+        if counter == 0 {
+            return self.start(now, index, sequence, frame);
+        }
+
+        let Some(index) = index else {
+            return Ok(None);
+        };
+        let session = self.sessions[index].as_mut().unwrap();
+
+        if now > session.deadline {
+            session.open = false;
+            return Err(TransportError::Timeout);
+        }
+
+        if sequence != session.sequence || counter != session.next {
+            session.open = false;
+            return Err(TransportError::BadSequence);
+        }
+
+        let len = (session.size - session.received).min(PACKET_LEN);
+        let payload = data.get(1..1 + len).ok_or_else(|| {
+            session.open = false;
+            TransportError::InvalidMessage
+        })?;
+
+        let start = session.received;
+        session.buffer[start..start + len].copy_from_slice(payload);
+        session.received += len;
+        session.next += 1;
+        session.deadline = now + TIMEOUT;
+
+        if session.received < session.size {
+            return Ok(None);
+        }
+
+        session.open = false;
+
+        Ok(Some(session.reassembled()))
+    }
+
+    fn start<F: Frame>(
+        &mut self,
+        now: Instant,
+        index: Option<usize>,
+        sequence: u8,
+        frame: &J1939Frame<'_, F>,
+    ) -> Result<Option<Reassembled<'_>>, TransportError> {
+        // a new first frame replaces any message in progress
+        if let Some(index) = index {
+            self.sessions[index].as_mut().unwrap().open = false;
+        }
+
+        let data = frame.data();
+        let size = data[1] as usize;
+
+        if size > MAX_LEN {
+            return Err(TransportError::TooLarge);
+        }
+
+        let len = size.min(FIRST_LEN);
+        let payload =
+            data.get(2..2 + len).ok_or(TransportError::InvalidMessage)?;
+
+        let index = index
+            .or_else(|| {
+                self.sessions.iter().position(|session| {
+                    session.as_ref().is_none_or(|session| !session.open)
+                })
+            })
+            .ok_or(TransportError::Busy)?;
+
+        let session = Session {
+            pgn: frame.pgn(),
+            source_address: frame.source_address(),
+            destination_address: frame.destination_address(),
+            sequence,
+            size,
+            next: 1,
+            received: len,
+            deadline: now + TIMEOUT,
+            open: size > len,
+            buffer: [0; MAX_LEN],
+        };
+        let session = self.sessions[index].insert(session);
+        session.buffer[..len].copy_from_slice(payload);
+
+        if session.open {
+            Ok(None)
+        } else {
+            Ok(Some(session.reassembled()))
+        }
+    }
+
+    fn find(&self, source_address: Address, pgn: Pgn) -> Option<usize> {
+        self.sessions.iter().position(|session| {
+            session.as_ref().is_some_and(|session| {
+                session.open
+                    && session.source_address == source_address
+                    && session.pgn == pgn
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{at, TestFrame};
+
+    const COG_SOG: [u8; 8] = [0x01, 0xFC, 0x10, 0x27, 0x64, 0x00, 0xFF, 0xFF];
+
+    fn id(source: u8) -> J1939Id {
+        J1939Id::broadcast(3, Pgn::new(129029).unwrap(), Address::new(source))
+            .unwrap()
+    }
+
+    #[test]
+    fn segment() {
+        let data: [u8; 20] = core::array::from_fn(|i| i as u8);
+        let mut sender = FastPacketSender::new(id(0x23), &data, 5).unwrap();
+
+        let first = sender.next().unwrap();
+        assert_eq!(first.id().as_raw(), 0x0DF8_0523);
+        assert_eq!(first.data(), &[0xA0, 20, 0, 1, 2, 3, 4, 5]);
+        assert_eq!(
+            sender.next().unwrap().data(),
+            &[0xA1, 6, 7, 8, 9, 10, 11, 12]
+        );
+        assert_eq!(
+            sender.next().unwrap().data(),
+            &[0xA2, 13, 14, 15, 16, 17, 18, 19]
+        );
+        assert_eq!(sender.next(), None);
+
+        // short messages still use a length byte
+        let mut sender = FastPacketSender::new(id(0x23), &[1, 2], 0).unwrap();
+        assert_eq!(
+            sender.next().unwrap().data(),
+            &[0x00, 2, 1, 2, 0xFF, 0xFF, 0xFF, 0xFF]
+        );
+        assert_eq!(sender.next(), None);
+
+        assert_eq!(frames(MAX_LEN), 32);
+        assert_eq!(
+            FastPacketSender::new(id(0x23), &[0; MAX_LEN + 1], 0).err(),
+            Some(TransportError::TooLarge)
+        );
+    }
+
+    #[test]
+    fn reassemble() {
+        let data: [u8; MAX_LEN] = core::array::from_fn(|i| i as u8);
+        let mut receiver = FastPacketReceiver::<2>::new();
+        let mut counter = SequenceCounter::new();
+        counter.advance();
+
+        let first: [TestFrame; 32] = {
+            let mut sender =
+                FastPacketSender::new(id(0x01), &data, counter.advance())
+                    .unwrap();
+            core::array::from_fn(|_| sender.next().unwrap().to_frame().unwrap())
+        };
+        let mut second =
+            FastPacketSender::new(id(0x02), &COG_SOG, counter.advance())
+                .unwrap()
+                .map(|packet| packet.to_frame::<TestFrame>().unwrap());
+
+        // interleave two sources
+        for (index, frame) in first.iter().enumerate() {
+            let view = J1939Frame::new(frame).unwrap();
+            let message = receiver.receive(at(index as u64), &view).unwrap();
+
+            if index == 31 {
+                let message = message.unwrap();
+                assert_eq!(message.source_address(), Address::new(0x01));
+                assert_eq!(message.data(), &data);
+            } else {
+                assert_eq!(message, None);
+            }
+
+            if let Some(frame) = second.next() {
+                let view = J1939Frame::new(&frame).unwrap();
+                let message =
+                    receiver.receive(at(index as u64), &view).unwrap();
+                if index == 1 {
+                    assert_eq!(message.unwrap().data(), &COG_SOG);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let data = [0; 20];
+        let mut receiver = FastPacketReceiver::<1>::new();
+        let frames: [TestFrame; 3] = {
+            let mut sender = FastPacketSender::new(id(0x01), &data, 1).unwrap();
+            core::array::from_fn(|_| sender.next().unwrap().to_frame().unwrap())
+        };
+
+        // frames without a first frame are ignored
+        let view = J1939Frame::new(&frames[1]).unwrap();
+        assert_eq!(receiver.receive(at(0), &view), Ok(None));
+
+        // skipped frame
+        let view = J1939Frame::new(&frames[0]).unwrap();
+        assert_eq!(receiver.receive(at(0), &view), Ok(None));
+        let view = J1939Frame::new(&frames[2]).unwrap();
+        assert_eq!(
+            receiver.receive(at(10), &view),
+            Err(TransportError::BadSequence)
+        );
+
+        // timeout
+        let view = J1939Frame::new(&frames[0]).unwrap();
+        assert_eq!(receiver.receive(at(100), &view), Ok(None));
+        assert_eq!(receiver.poll(at(850)), Ok(()));
+        assert_eq!(receiver.poll(at(851)), Err(TransportError::Timeout));
+
+        // one session per source and parameter group
+        let view = J1939Frame::new(&frames[0]).unwrap();
+        assert_eq!(receiver.receive(at(900), &view), Ok(None));
+        let other: TestFrame = FastPacketSender::new(id(0x02), &data, 1)
+            .unwrap()
+            .next()
+            .unwrap()
+            .to_frame()
+            .unwrap();
+        let view = J1939Frame::new(&other).unwrap();
+        assert_eq!(receiver.receive(at(900), &view), Err(TransportError::Busy));
+    }
+}
@@ -0,0 +1,539 @@
+//! Transport protocol.
+//!
+//! Messages longer than 8 bytes are split into packets of 7 bytes and sent
+//! using the connection management (TP.CM) and data transfer (TP.DT)
+//! parameter groups. Broadcasts are announced with a broadcast announce
+//! message (BAM) and sent to the global address. Messages to a single node
+//! use a connection with flow control, set up with a request to send (RTS)
+//! and paced by the receiver with clear to send (CTS) messages. Messages
+//! larger than [`MAX_LEN`] use the [`extended`] transport protocol. NMEA
+//! 2000 networks send most multi-frame messages as a [`fast_packet`] instead.
+//!
+//! See SAEJ1939-21 5.10
+
+pub mod bam;
+pub mod connection;
+pub mod extended;
+pub mod fast_packet;
+pub mod session;
+pub mod sniffer;
+
+use crate::error::MessageError;
+use crate::identifier::{Address, J1939Id, Pgn};
+use core::time::Duration;
+use embedded_can::Frame;
+
+/// Connection management parameter group number (TP.CM).
+pub const CONNECTION_MANAGEMENT: Pgn = Pgn::from_parts(false, false, 0xEC, 0);
+
+/// Data transfer parameter group number (TP.DT).
+pub const DATA_TRANSFER: Pgn = Pgn::from_parts(false, false, 0xEB, 0);
+
+/// Default priority for transport protocol messages.
+pub const PRIORITY: u8 = 7;
+
+/// Largest message that can be sent using the transport protocol.
+pub const MAX_LEN: usize = 1785;
+
+/// Number of data bytes in each data transfer packet.
+pub const PACKET_LEN: usize = 7;
+
+/// Largest number of packets in a single message.
+pub const MAX_PACKETS: u8 = 255;
+
+/// Shortest time between broadcast packets.
+pub const BAM_MIN_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Longest time between broadcast packets.
+pub const BAM_MAX_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Time a node has to respond.
+pub const TR: Duration = Duration::from_millis(200);
+
+/// Time between clear to send messages while a connection is held open.
+pub const TH: Duration = Duration::from_millis(500);
+
+/// Time allowed between received data packets.
+pub const T1: Duration = Duration::from_millis(750);
+
+/// Time allowed between sending a clear to send and the first data packet.
+pub const T2: Duration = Duration::from_millis(1250);
+
+/// Time allowed between sending the last data packet of a window, or the
+/// request to send, and the next clear to send or end of message
+/// acknowledgement.
+pub const T3: Duration = Duration::from_millis(1250);
+
+/// Time allowed after a clear to send holding the connection open.
+pub const T4: Duration = Duration::from_millis(1050);
+
+/// Reason a connection was aborted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AbortReason {
+    /// Already in one or more connections and can't support another.
+    AlreadyInSession,
+    /// System resources were needed for another task.
+    ResourcesNeeded,
+    /// A timeout occurred.
+    Timeout,
+    /// Clear to send received while data transfer is in progress.
+    CtsWhileSending,
+    /// Maximum retransmit request limit reached.
+    MaxRetransmit,
+    /// Unexpected data transfer packet.
+    UnexpectedDataTransfer,
+    /// Bad sequence number.
+    BadSequence,
+    /// Duplicate sequence number.
+    DuplicateSequence,
+    /// Unexpected extended data packet offset packet.
+    UnexpectedDataPacketOffset,
+    /// Unexpected extended data packet offset parameter group.
+    UnexpectedDataPacketOffsetPgn,
+    /// Extended data packet offset number of packets is greater than clear
+    /// to send.
+    DataPacketOffsetTooLarge,
+    /// Bad extended data packet offset.
+    BadDataPacketOffset,
+    /// Unexpected extended clear to send parameter group.
+    UnexpectedCtsPgn,
+    /// Extended clear to send requested packets exceeds message size.
+    CtsTooLarge,
+    /// Any other reason.
+    Other,
+    /// Reserved or unknown reason.
+    Reserved(u8),
+}
+
+impl From<u8> for AbortReason {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::AlreadyInSession,
+            2 => Self::ResourcesNeeded,
+            3 => Self::Timeout,
+            4 => Self::CtsWhileSending,
+            5 => Self::MaxRetransmit,
+            6 => Self::UnexpectedDataTransfer,
+            7 => Self::BadSequence,
+            8 => Self::DuplicateSequence,
+            9 => Self::UnexpectedDataPacketOffset,
+            10 => Self::UnexpectedDataPacketOffsetPgn,
+            11 => Self::DataPacketOffsetTooLarge,
+            12 => Self::BadDataPacketOffset,
+            14 => Self::UnexpectedCtsPgn,
+            15 => Self::CtsTooLarge,
+            250 => Self::Other,
+            value => Self::Reserved(value),
+        }
+    }
+}
+
+impl From<AbortReason> for u8 {
+    fn from(value: AbortReason) -> u8 {
+        match value {
+            AbortReason::AlreadyInSession => 1,
+            AbortReason::ResourcesNeeded => 2,
+            AbortReason::Timeout => 3,
+            AbortReason::CtsWhileSending => 4,
+            AbortReason::MaxRetransmit => 5,
+            AbortReason::UnexpectedDataTransfer => 6,
+            AbortReason::BadSequence => 7,
+            AbortReason::DuplicateSequence => 8,
+            AbortReason::UnexpectedDataPacketOffset => 9,
+            AbortReason::UnexpectedDataPacketOffsetPgn => 10,
+            AbortReason::DataPacketOffsetTooLarge => 11,
+            AbortReason::BadDataPacketOffset => 12,
+            AbortReason::UnexpectedCtsPgn => 14,
+            AbortReason::CtsTooLarge => 15,
+            AbortReason::Other => 250,
+            AbortReason::Reserved(value) => value,
+        }
+    }
+}
+
+/// Connection management message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionManagement {
+    /// Request to send.
+    Rts {
+        /// Total message size in bytes.
+        size: u16,
+        /// Total number of packets.
+        packets: u8,
+        /// Largest number of packets the sender can send per clear to send,
+        /// `None` if there is no limit.
+        max_packets: Option<u8>,
+        /// Parameter group being sent.
+        pgn: Pgn,
+    },
+    /// Clear to send.
+    Cts {
+        /// Number of packets that can be sent, zero to hold the connection
+        /// open.
+        packets: u8,
+        /// Sequence number of the next packet to send.
+        next: u8,
+        /// Parameter group being sent.
+        pgn: Pgn,
+    },
+    /// End of message acknowledgement.
+    EndOfMsgAck {
+        /// Total message size in bytes.
+        size: u16,
+        /// Total number of packets.
+        packets: u8,
+        /// Parameter group that was sent.
+        pgn: Pgn,
+    },
+    /// Connection abort.
+    Abort {
+        /// Why the connection was aborted.
+        reason: AbortReason,
+        /// Parameter group being sent.
+        pgn: Pgn,
+    },
+    /// Broadcast announce message.
+    Bam {
+        /// Total message size in bytes.
+        size: u16,
+        /// Total number of packets.
+        packets: u8,
+        /// Parameter group being sent.
+        pgn: Pgn,
+    },
+}
+
+impl ConnectionManagement {
+    const RTS: u8 = 16;
+    const CTS: u8 = 17;
+    const END_OF_MSG_ACK: u8 = 19;
+    const BAM: u8 = 32;
+    const ABORT: u8 = 255;
+
+    /// Decode a connection management payload.
+    pub fn decode(data: &[u8]) -> Result<Self, MessageError> {
+        if data.len() != 8 {
+            return Err(MessageError::InvalidLength);
+        }
+
+        let pgn = Pgn::new(u32::from_le_bytes([data[5], data[6], data[7], 0]))
+            .ok_or(MessageError::InvalidPgn)?;
+        let size = u16::from_le_bytes([data[1], data[2]]);
+
+        match data[0] {
+            Self::RTS => Ok(Self::Rts {
+                size,
+                packets: data[3],
+                max_packets: match data[4] {
+                    0xFF => None,
+                    value => Some(value),
+                },
+                pgn,
+            }),
+            Self::CTS => Ok(Self::Cts {
+                packets: data[1],
+                next: data[2],
+                pgn,
+            }),
+            Self::END_OF_MSG_ACK => Ok(Self::EndOfMsgAck {
+                size,
+                packets: data[3],
+                pgn,
+            }),
+            Self::ABORT => Ok(Self::Abort {
+                reason: AbortReason::from(data[1]),
+                pgn,
+            }),
+            Self::BAM => Ok(Self::Bam {
+                size,
+                packets: data[3],
+                pgn,
+            }),
+            _ => Err(MessageError::InvalidControlByte),
+        }
+    }
+
+    /// Encode the connection management payload.
+    pub fn encode(&self) -> [u8; 8] {
+        let (control, a, b, c, d, pgn) = match *self {
+            Self::Rts {
+                size,
+                packets,
+                max_packets,
+                pgn,
+            } => {
+                let size = size.to_le_bytes();
+                let max_packets = max_packets.unwrap_or(0xFF);
+                (Self::RTS, size[0], size[1], packets, max_packets, pgn)
+            }
+            Self::Cts { packets, next, pgn } => {
+                (Self::CTS, packets, next, 0xFF, 0xFF, pgn)
+            }
+            Self::EndOfMsgAck { size, packets, pgn } => {
+                let size = size.to_le_bytes();
+                (Self::END_OF_MSG_ACK, size[0], size[1], packets, 0xFF, pgn)
+            }
+            Self::Abort { reason, pgn } => {
+                (Self::ABORT, reason.into(), 0xFF, 0xFF, 0xFF, pgn)
+            }
+            Self::Bam { size, packets, pgn } => {
+                let size = size.to_le_bytes();
+                (Self::BAM, size[0], size[1], packets, 0xFF, pgn)
+            }
+        };
+        let pgn = pgn.as_raw().to_le_bytes();
+
+        [control, a, b, c, d, pgn[0], pgn[1], pgn[2]]
+    }
+}
+
+/// Data transfer packet view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataTransfer<'a>(&'a [u8]);
+
+impl<'a> DataTransfer<'a> {
+    /// Create a view over a data transfer payload.
+    pub fn new(data: &'a [u8]) -> Result<Self, MessageError> {
+        if data.len() != 8 {
+            return Err(MessageError::InvalidLength);
+        }
+
+        Ok(Self(data))
+    }
+
+    /// Encode a data transfer payload.
+    ///
+    /// Bytes past the end of `data` are padded with `0xFF`.
+    pub fn encode(sequence: u8, data: &[u8]) -> [u8; 8] {
+        let mut packet = [0xFF; 8];
+        packet[0] = sequence;
+        packet[1..1 + data.len()].copy_from_slice(data);
+        packet
+    }
+
+    /// Sequence number, starting at 1.
+    pub fn sequence(&self) -> u8 {
+        self.0[0]
+    }
+
+    /// Packet data, including any padding on the last packet.
+    pub fn data(&self) -> &'a [u8] {
+        &self.0[1..]
+    }
+}
+
+/// Number of packets needed to send `size` bytes.
+pub const fn packets(size: usize) -> usize {
+    size.div_ceil(PACKET_LEN)
+}
+
+/// Frame to be sent by a transport protocol state machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packet {
+    id: J1939Id,
+    data: [u8; 8],
+}
+
+impl Packet {
+    fn connection_management(
+        message: ConnectionManagement,
+        destination_address: Address,
+        source_address: Address,
+    ) -> Self {
+        Self::new(
+            CONNECTION_MANAGEMENT,
+            message.encode(),
+            destination_address,
+            source_address,
+        )
+    }
+
+    fn new(
+        pgn: Pgn,
+        data: [u8; 8],
+        destination_address: Address,
+        source_address: Address,
+    ) -> Self {
+        // transport protocol groups are PDU1 and the priority is in range
+        let id =
+            J1939Id::new(PRIORITY, pgn, destination_address, source_address)
+                .unwrap();
+
+        Self { id, data }
+    }
+
+    /// Identifier to send the frame with.
+    pub fn id(&self) -> J1939Id {
+        self.id
+    }
+
+    /// Frame payload.
+    pub fn data(&self) -> &[u8; 8] {
+        &self.data
+    }
+
+    /// Create the frame.
+    pub fn to_frame<F: Frame>(&self) -> Option<F> {
+        F::new(self.id, &self.data)
+    }
+}
+
+/// Progress of a transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Status {
+    /// Transfer is still in progress.
+    Active,
+    /// All data has been sent and acknowledged.
+    Complete,
+    /// Transfer was aborted.
+    Aborted(AbortReason),
+}
+
+/// Message reassembled by the transport protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reassembled<'a> {
+    pgn: Pgn,
+    source_address: Address,
+    destination_address: Address,
+    data: &'a [u8],
+}
+
+impl<'a> Reassembled<'a> {
+    /// Parameter group number of the message.
+    pub fn pgn(&self) -> Pgn {
+        self.pgn
+    }
+
+    /// Node that sent the message.
+    pub fn source_address(&self) -> Address {
+        self.source_address
+    }
+
+    /// Node the message was sent to, global for broadcasts.
+    pub fn destination_address(&self) -> Address {
+        self.destination_address
+    }
+
+    /// Message payload.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bam() {
+        // DM1 with two DTCs, see J1939-21 5.10.3.5
+        let data = [0x20, 0x0A, 0x00, 0x02, 0xFF, 0xCA, 0xFE, 0x00];
+        let bam = ConnectionManagement::decode(&data).unwrap();
+
+        assert_eq!(
+            bam,
+            ConnectionManagement::Bam {
+                size: 10,
+                packets: 2,
+                pgn: Pgn::new(65226).unwrap(),
+            }
+        );
+        assert_eq!(bam.encode(), data);
+    }
+
+    #[test]
+    fn connection() {
+        let pgn = Pgn::new(0xD800).unwrap();
+        let messages = [
+            (
+                [0x10, 0x17, 0x00, 0x04, 0xFF, 0x00, 0xD8, 0x00],
+                ConnectionManagement::Rts {
+                    size: 23,
+                    packets: 4,
+                    max_packets: None,
+                    pgn,
+                },
+            ),
+            (
+                [0x11, 0x02, 0x01, 0xFF, 0xFF, 0x00, 0xD8, 0x00],
+                ConnectionManagement::Cts {
+                    packets: 2,
+                    next: 1,
+                    pgn,
+                },
+            ),
+            (
+                [0x13, 0x17, 0x00, 0x04, 0xFF, 0x00, 0xD8, 0x00],
+                ConnectionManagement::EndOfMsgAck {
+                    size: 23,
+                    packets: 4,
+                    pgn,
+                },
+            ),
+            (
+                [0xFF, 0x03, 0xFF, 0xFF, 0xFF, 0x00, 0xD8, 0x00],
+                ConnectionManagement::Abort {
+                    reason: AbortReason::Timeout,
+                    pgn,
+                },
+            ),
+        ];
+
+        for (data, message) in messages {
+            assert_eq!(ConnectionManagement::decode(&data), Ok(message));
+            assert_eq!(message.encode(), data);
+        }
+    }
+
+    #[test]
+    fn abort_reasons() {
+        for value in 0..=255 {
+            assert_eq!(u8::from(AbortReason::from(value)), value);
+        }
+
+        assert_eq!(AbortReason::from(7), AbortReason::BadSequence);
+        assert_eq!(AbortReason::from(250), AbortReason::Other);
+        assert_eq!(AbortReason::from(13), AbortReason::Reserved(13));
+    }
+
+    #[test]
+    fn data_transfer() {
+        let data = DataTransfer::encode(2, &[1, 2, 3]);
+        assert_eq!(data, [2, 1, 2, 3, 0xFF, 0xFF, 0xFF, 0xFF]);
+
+        let packet = DataTransfer::new(&data).unwrap();
+        assert_eq!(packet.sequence(), 2);
+        assert_eq!(packet.data(), &[1, 2, 3, 0xFF, 0xFF, 0xFF, 0xFF]);
+
+        assert_eq!(packets(9), 2);
+        assert_eq!(packets(14), 2);
+        assert_eq!(packets(MAX_LEN), 255);
+    }
+
+    #[test]
+    fn errors() {
+        let data = [0x21, 0x0A, 0x00, 0x02, 0xFF, 0xCA, 0xFE, 0x00];
+        assert_eq!(
+            ConnectionManagement::decode(&data),
+            Err(MessageError::InvalidControlByte)
+        );
+
+        let data = [0x10, 0x0A, 0x00, 0x02, 0xFF, 0xCA, 0xFE, 0x04];
+        assert_eq!(
+            ConnectionManagement::decode(&data),
+            Err(MessageError::InvalidPgn)
+        );
+
+        let data = [0x12, 0x0A, 0x00, 0x02, 0xFF, 0xCA, 0xFE, 0x00];
+        assert_eq!(
+            ConnectionManagement::decode(&data),
+            Err(MessageError::InvalidControlByte)
+        );
+        assert_eq!(
+            ConnectionManagement::decode(&data[..7]),
+            Err(MessageError::InvalidLength)
+        );
+    }
+}
@@ -0,0 +1,497 @@
+//! Concurrent transport sessions.
+//!
+//! A node may run one broadcast per source and one connection per source and
+//! destination pair at the same time. [`SessionTable`] keeps a receiver for
+//! each pair, up to a fixed capacity.
+
+use super::bam::BamReceiver;
+use super::connection::ConnectionReceiver;
+use super::{
+    AbortReason, ConnectionManagement, Packet, Reassembled,
+    CONNECTION_MANAGEMENT, DATA_TRANSFER, MAX_LEN,
+};
+use crate::error::TransportError;
+use crate::frame::J1939Frame;
+use crate::identifier::{Address, Pgn};
+use crate::time::Instant;
+use embedded_can::Frame;
+
+/// Aborts queued between two calls to `poll`.
+const ABORTS: usize = 4;
+
+/// What to do with a new session when the table is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Eviction {
+    /// Refuse the new session.
+    ///
+    /// Connections are aborted with [`AbortReason::AlreadyInSession`].
+    #[default]
+    Refuse,
+    /// Drop the session that has gone the longest without receiving a frame.
+    ///
+    /// Dropped connections are aborted with
+    /// [`AbortReason::ResourcesNeeded`].
+    LeastRecent,
+}
+
+#[derive(Debug, Clone)]
+enum Receiver<const N: usize> {
+    Bam(BamReceiver<N>),
+    Connection(ConnectionReceiver<N>),
+}
+
+#[derive(Debug, Clone)]
+struct Entry<const N: usize> {
+    source_address: Address,
+    destination_address: Address,
+    active: Instant,
+    receiver: Receiver<N>,
+}
+
+impl<const N: usize> Entry<N> {
+    fn is_idle(&self) -> bool {
+        match &self.receiver {
+            Receiver::Bam(receiver) => receiver.source_address().is_none(),
+            Receiver::Connection(receiver) => receiver.is_idle(),
+        }
+    }
+}
+
+/// Receives up to `S` broadcasts and connections at the same time.
+///
+/// Connections are accepted to any specific destination, so only frames for
+/// the addresses this node owns should be passed in. Each session has a
+/// buffer of `N` bytes, which defaults to the largest transport protocol
+/// message.
+#[derive(Debug, Clone)]
+pub struct SessionTable<const S: usize, const N: usize = MAX_LEN> {
+    window: u8,
+    eviction: Eviction,
+    entries: [Option<Entry<N>>; S],
+    /// Aborts to send, oldest first.
+    aborts: [Option<Packet>; ABORTS],
+}
+
+impl<const S: usize, const N: usize> SessionTable<S, N> {
+    /// Create an empty table.
+    ///
+    /// `window` is the largest number of packets asked for with each clear
+    /// to send.
+    pub const fn new(window: u8, eviction: Eviction) -> Self {
+        Self {
+            window,
+            eviction,
+            entries: [const { None }; S],
+            aborts: [const { None }; ABORTS],
+        }
+    }
+
+    /// Largest number of concurrent sessions.
+    pub fn capacity(&self) -> usize {
+        S
+    }
+
+    /// Number of sessions in progress.
+    pub fn len(&self) -> usize {
+        self.entries
+            .iter()
+            .flatten()
+            .filter(|entry| !entry.is_idle())
+            .count()
+    }
+
+    /// Whether no sessions are in progress.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Abort the connection between `source_address` and
+    /// `destination_address`, if any.
+    pub fn abort(
+        &mut self,
+        source_address: Address,
+        destination_address: Address,
+        reason: AbortReason,
+    ) {
+        if let Some(index) = self.find(source_address, destination_address) {
+            if let Some(Entry {
+                receiver: Receiver::Connection(receiver),
+                ..
+            }) = &mut self.entries[index]
+            {
+                receiver.abort(reason);
+            }
+        }
+    }
+
+    /// Next frame to send, if any.
+    ///
+    /// Broadcasts that timed out are dropped.
+    pub fn poll(&mut self, now: Instant) -> Option<Packet> {
+        if let Some(packet) = self.aborts[0].take() {
+            self.aborts.rotate_left(1);
+            return Some(packet);
+        }
+
+        for entry in self.entries.iter_mut().flatten() {
+            match &mut entry.receiver {
+                Receiver::Bam(receiver) => {
+                    receiver.poll(now).ok();
+                }
+                Receiver::Connection(receiver) => {
+                    if let Some(packet) = receiver.poll(now) {
+                        return Some(packet);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Process a received frame.
+    ///
+    /// Returns a message once its last packet has been received. When the
+    /// table is full a new session is refused with [`TransportError::Busy`],
+    /// or replaces another session depending on the eviction policy.
+    pub fn receive<F: Frame>(
+        &mut self,
+        now: Instant,
+        frame: &J1939Frame<'_, F>,
+    ) -> Result<Option<Reassembled<'_>>, TransportError> {
+        let source_address = frame.source_address();
+        let destination_address = frame.destination_address();
+        let existing = self.find(source_address, destination_address);
+
+        let index = match (frame.pgn(), existing) {
+            (CONNECTION_MANAGEMENT | DATA_TRANSFER, Some(index)) => index,
+            (CONNECTION_MANAGEMENT, None) => {
+                let receiver = match ConnectionManagement::decode(frame.data())?
+                {
+                    ConnectionManagement::Bam { .. }
+                        if destination_address.is_global() =>
+                    {
+                        Receiver::Bam(BamReceiver::new())
+                    }
+                    ConnectionManagement::Rts { pgn, .. }
+                        if destination_address.is_unicast() =>
+                    {
+                        if self.eviction == Eviction::Refuse
+                            && self.free().is_none()
+                        {
+                            self.refuse(
+                                pgn,
+                                source_address,
+                                destination_address,
+                            );
+                            return Err(TransportError::Busy);
+                        }
+
+                        Receiver::Connection(ConnectionReceiver::new(
+                            destination_address,
+                            self.window,
+                        ))
+                    }
+                    _ => return Ok(None),
+                };
+
+                self.insert(
+                    now,
+                    Entry {
+                        source_address,
+                        destination_address,
+                        active: now,
+                        receiver,
+                    },
+                )?
+            }
+            _ => return Ok(None),
+        };
+
+        // the index was either found or just inserted
+        let entry = self.entries[index].as_mut().unwrap();
+        entry.active = now;
+
+        match &mut entry.receiver {
+            Receiver::Bam(receiver) => receiver.receive(now, frame),
+            Receiver::Connection(receiver) => receiver.receive(now, frame),
+        }
+    }
+
+    fn find(
+        &self,
+        source_address: Address,
+        destination_address: Address,
+    ) -> Option<usize> {
+        self.entries.iter().position(|entry| {
+            entry.as_ref().is_some_and(|entry| {
+                entry.source_address == source_address
+                    && entry.destination_address == destination_address
+            })
+        })
+    }
+
+    /// Queue an abort refusing a connection.
+    fn refuse(
+        &mut self,
+        pgn: Pgn,
+        source_address: Address,
+        destination_address: Address,
+    ) {
+        self.queue(Packet::connection_management(
+            ConnectionManagement::Abort {
+                reason: AbortReason::AlreadyInSession,
+                pgn,
+            },
+            source_address,
+            destination_address,
+        ));
+    }
+
+    /// Queue an abort, dropping it when the queue is full. The sender then
+    /// times out instead.
+    fn queue(&mut self, packet: Packet) {
+        if let Some(slot) = self.aborts.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(packet);
+        }
+    }
+
+    fn free(&self) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|entry| entry.as_ref().is_none_or(Entry::is_idle))
+    }
+
+    fn insert(
+        &mut self,
+        now: Instant,
+        entry: Entry<N>,
+    ) -> Result<usize, TransportError> {
+        let index = match (self.free(), self.eviction) {
+            (Some(index), _) => index,
+            (None, Eviction::Refuse) => return Err(TransportError::Busy),
+            (None, Eviction::LeastRecent) => {
+                let index = self
+                    .entries
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, entry)| {
+                        entry.as_ref().map(|entry| (index, entry.active))
+                    })
+                    .min_by_key(|(_, active)| *active)
+                    .map(|(index, _)| index)
+                    .ok_or(TransportError::Busy)?;
+
+                if let Some(Entry {
+                    receiver: Receiver::Connection(receiver),
+                    ..
+                }) = &mut self.entries[index]
+                {
+                    receiver.abort(AbortReason::ResourcesNeeded);
+                    if let Some(packet) = receiver.poll(now) {
+                        self.queue(packet);
+                    }
+                }
+
+                index
+            }
+        };
+
+        self.entries[index] = Some(entry);
+
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{at, control, frame, TestFrame};
+
+    fn bam(source: u8) -> TestFrame {
+        frame(
+            0x1CEC_FF00 | source as u32,
+            &[0x20, 10, 0, 2, 0xFF, 0xCA, 0xFE, 0x00],
+        )
+    }
+
+    fn dt(source: u8, sequence: u8) -> TestFrame {
+        frame(
+            0x1CEB_FF00 | source as u32,
+            &[
+                sequence, source, source, source, source, source, source,
+                source,
+            ],
+        )
+    }
+
+    fn rts(source: u8) -> TestFrame {
+        frame(
+            0x1CEC_0000 | source as u32,
+            &[0x10, 16, 0, 3, 0xFF, 0x00, 0xD8, 0x00],
+        )
+    }
+
+    #[test]
+    fn interleaved() {
+        let mut table = SessionTable::<4, 32>::new(16, Eviction::Refuse);
+
+        for source in [1, 2, 3] {
+            let frame = bam(source);
+            let view = J1939Frame::new(&frame).unwrap();
+            assert_eq!(table.receive(at(0), &view), Ok(None));
+        }
+        assert_eq!(table.len(), 3);
+
+        for sequence in [1, 2] {
+            for source in [3, 1, 2] {
+                let frame = dt(source, sequence);
+                let view = J1939Frame::new(&frame).unwrap();
+                let message = table.receive(at(10), &view).unwrap();
+
+                if sequence == 2 {
+                    let message = message.unwrap();
+                    assert_eq!(message.pgn(), Pgn::new(65226).unwrap());
+                    assert_eq!(message.source_address(), Address::new(source));
+                    assert_eq!(message.data(), &[source; 10]);
+                } else {
+                    assert_eq!(message, None);
+                }
+            }
+        }
+
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn refuse() {
+        let mut table = SessionTable::<2, 32>::new(16, Eviction::Refuse);
+
+        for source in [1, 2] {
+            let frame = bam(source);
+            table
+                .receive(at(0), &J1939Frame::new(&frame).unwrap())
+                .unwrap();
+        }
+
+        let frame = bam(3);
+        assert_eq!(
+            table.receive(at(0), &J1939Frame::new(&frame).unwrap()),
+            Err(TransportError::Busy)
+        );
+
+        let frame = rts(3);
+        assert_eq!(
+            table.receive(at(0), &J1939Frame::new(&frame).unwrap()),
+            Err(TransportError::Busy)
+        );
+
+        let abort = table.poll(at(0)).unwrap();
+        assert_eq!(abort.id().as_raw(), 0x1CEC_0300);
+        assert_eq!(
+            control(&abort),
+            ConnectionManagement::Abort {
+                reason: AbortReason::AlreadyInSession,
+                pgn: Pgn::new(0xD800).unwrap(),
+            }
+        );
+        assert_eq!(table.poll(at(0)), None);
+
+        // every refused connection gets its abort
+        for source in [4, 5] {
+            let frame = rts(source);
+            table
+                .receive(at(0), &J1939Frame::new(&frame).unwrap())
+                .unwrap_err();
+        }
+        assert_eq!(table.poll(at(0)).unwrap().id().as_raw(), 0x1CEC_0400);
+        assert_eq!(table.poll(at(0)).unwrap().id().as_raw(), 0x1CEC_0500);
+        assert_eq!(table.poll(at(0)), None);
+
+        // timed out broadcasts free their slot
+        table.poll(at(1000));
+        assert!(table.is_empty());
+        let frame = rts(3);
+        table
+            .receive(at(1000), &J1939Frame::new(&frame).unwrap())
+            .unwrap();
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn least_recent() {
+        let mut table = SessionTable::<2, 32>::new(16, Eviction::LeastRecent);
+
+        let frame = rts(1);
+        table
+            .receive(at(0), &J1939Frame::new(&frame).unwrap())
+            .unwrap();
+        assert!(matches!(
+            control(&table.poll(at(0)).unwrap()),
+            ConnectionManagement::Cts { packets: 3, .. }
+        ));
+
+        let frame = bam(2);
+        table
+            .receive(at(10), &J1939Frame::new(&frame).unwrap())
+            .unwrap();
+
+        // the connection has been quiet the longest
+        let frame = bam(3);
+        table
+            .receive(at(20), &J1939Frame::new(&frame).unwrap())
+            .unwrap();
+
+        let abort = table.poll(at(20)).unwrap();
+        assert_eq!(abort.id().as_raw(), 0x1CEC_0100);
+        assert_eq!(
+            control(&abort),
+            ConnectionManagement::Abort {
+                reason: AbortReason::ResourcesNeeded,
+                pgn: Pgn::new(0xD800).unwrap(),
+            }
+        );
+        assert_eq!(table.len(), 2);
+
+        let frame = dt(2, 1);
+        assert_eq!(
+            table.receive(at(30), &J1939Frame::new(&frame).unwrap()),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn evict_several() {
+        let mut table = SessionTable::<2, 32>::new(16, Eviction::LeastRecent);
+
+        for source in [1, 2] {
+            let frame = rts(source);
+            table
+                .receive(at(source as u64), &J1939Frame::new(&frame).unwrap())
+                .unwrap();
+            table.poll(at(source as u64)).unwrap();
+        }
+
+        // both connections are evicted before the next poll
+        for source in [3, 4] {
+            let frame = bam(source);
+            table
+                .receive(at(10), &J1939Frame::new(&frame).unwrap())
+                .unwrap();
+        }
+
+        for destination in [1, 2] {
+            let abort = table.poll(at(10)).unwrap();
+            assert_eq!(abort.id().as_raw(), 0x1CEC_0000 | destination << 8);
+            assert!(matches!(
+                control(&abort),
+                ConnectionManagement::Abort {
+                    reason: AbortReason::ResourcesNeeded,
+                    ..
+                }
+            ));
+        }
+        assert_eq!(table.poll(at(10)), None);
+    }
+}
@@ -0,0 +1,468 @@
+//! Passive transport protocol listener.
+//!
+//! Follows broadcasts and connections between other nodes without sending
+//! anything, for logging and diagnostics.
+
+use super::{
+    packets, ConnectionManagement, DataTransfer, Reassembled,
+    CONNECTION_MANAGEMENT, DATA_TRANSFER, MAX_LEN, PACKET_LEN, T1, T3,
+};
+use crate::error::TransportError;
+use crate::frame::J1939Frame;
+use crate::identifier::{Address, Pgn};
+use crate::time::Instant;
+use embedded_can::Frame;
+
+/// Session being followed.
+#[derive(Debug, Clone)]
+struct Session<const N: usize> {
+    pgn: Pgn,
+    source_address: Address,
+    destination_address: Address,
+    size: usize,
+    packets: u8,
+    /// Bit for each sequence number received.
+    received: [u32; 8],
+    deadline: Instant,
+    /// Cleared once the session is over, the buffer is kept until the slot
+    /// is reused.
+    open: bool,
+    buffer: [u8; N],
+}
+
+impl<const N: usize> Session<N> {
+    fn is_broadcast(&self) -> bool {
+        self.destination_address.is_global()
+    }
+
+    fn is_complete(&self) -> bool {
+        (1..=self.packets).all(|sequence| {
+            self.received[sequence as usize / 32] & (1 << (sequence % 32)) != 0
+        })
+    }
+
+    fn timeout(&self) -> core::time::Duration {
+        if self.is_broadcast() {
+            T1
+        } else {
+            T3
+        }
+    }
+
+    fn reassembled(&self) -> Reassembled<'_> {
+        Reassembled {
+            pgn: self.pgn,
+            source_address: self.source_address,
+            destination_address: self.destination_address,
+            data: &self.buffer[..self.size],
+        }
+    }
+}
+
+/// Reassembles up to `S` broadcasts and connections between other nodes.
+///
+/// Packets are stored by sequence number, so windows the receiver asks to be
+/// sent again simply overwrite what was received before. Broadcasts are
+/// returned once every packet has been seen, connections once the receiver
+/// acknowledges the end of the message.
+#[derive(Debug, Clone)]
+pub struct Sniffer<const S: usize, const N: usize = MAX_LEN> {
+    sessions: [Option<Session<N>>; S],
+}
+
+impl<const S: usize, const N: usize> Default for Sniffer<S, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const S: usize, const N: usize> Sniffer<S, N> {
+    /// Create an idle listener.
+    pub const fn new() -> Self {
+        Self {
+            sessions: [const { None }; S],
+        }
+    }
+
+    /// Number of sessions being followed.
+    pub fn len(&self) -> usize {
+        self.sessions
+            .iter()
+            .flatten()
+            .filter(|session| session.open)
+            .count()
+    }
+
+    /// Whether no sessions are being followed.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop sessions that timed out.
+    ///
+    /// Returns [`TransportError::Timeout`] when any session was dropped.
+    pub fn poll(&mut self, now: Instant) -> Result<(), TransportError> {
+        let mut result = Ok(());
+
+        for session in self.sessions.iter_mut().flatten() {
+            if session.open && now > session.deadline {
+                session.open = false;
+                result = Err(TransportError::Timeout);
+            }
+        }
+
+        result
+    }
+
+    /// Process a frame seen on the bus.
+    ///
+    /// Returns a message once it has been completely transferred. Aborts
+    /// sent by either node are returned as [`TransportError::Aborted`].
+    pub fn receive<F: Frame>(
+        &mut self,
+        now: Instant,
+        frame: &J1939Frame<'_, F>,
+    ) -> Result<Option<Reassembled<'_>>, TransportError> {
+        match frame.pgn() {
+            CONNECTION_MANAGEMENT => self.connection_management(now, frame),
+            DATA_TRANSFER => self.data_transfer(now, frame),
+            _ => Ok(None),
+        }
+    }
+
+    fn connection_management<F: Frame>(
+        &mut self,
+        now: Instant,
+        frame: &J1939Frame<'_, F>,
+    ) -> Result<Option<Reassembled<'_>>, TransportError> {
+        let source_address = frame.source_address();
+        let destination_address = frame.destination_address();
+
+        match ConnectionManagement::decode(frame.data())? {
+            ConnectionManagement::Bam { size, packets, pgn }
+                if destination_address.is_global() =>
+            {
+                self.start(
+                    now,
+                    pgn,
+                    source_address,
+                    destination_address,
+                    size as usize,
+                    packets,
+                )?;
+                Ok(None)
+            }
+            ConnectionManagement::Rts {
+                size, packets, pgn, ..
+            } if destination_address.is_unicast() => {
+                self.start(
+                    now,
+                    pgn,
+                    source_address,
+                    destination_address,
+                    size as usize,
+                    packets,
+                )?;
+                Ok(None)
+            }
+            // flow control is sent by the receiver back to the sender
+            ConnectionManagement::Cts {
+                packets: count,
+                next,
+                ..
+            } => {
+                let Some(index) =
+                    self.find(destination_address, source_address)
+                else {
+                    return Ok(None);
+                };
+                let session = self.sessions[index].as_mut().unwrap();
+
+                if count > 0
+                    && (next == 0
+                        || next as usize + count as usize - 1
+                            > session.packets as usize)
+                {
+                    session.open = false;
+                    return Err(TransportError::InvalidMessage);
+                }
+
+                session.deadline = now + T3;
+                Ok(None)
+            }
+            ConnectionManagement::EndOfMsgAck { .. } => {
+                let Some(index) =
+                    self.find(destination_address, source_address)
+                else {
+                    return Ok(None);
+                };
+                let session = self.sessions[index].as_mut().unwrap();
+                session.open = false;
+
+                if !session.is_complete() {
+                    return Err(TransportError::BadSequence);
+                }
+
+                Ok(Some(session.reassembled()))
+            }
+            ConnectionManagement::Abort { .. } => {
+                let index = self
+                    .find(source_address, destination_address)
+                    .or_else(|| self.find(destination_address, source_address));
+
+                match index {
+                    Some(index) => {
+                        self.sessions[index].as_mut().unwrap().open = false;
+                        Err(TransportError::Aborted)
+                    }
+                    None => Ok(None),
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn data_transfer<F: Frame>(
+        &mut self,
+        now: Instant,
+        frame: &J1939Frame<'_, F>,
+    ) -> Result<Option<Reassembled<'_>>, TransportError> {
+        let Some(index) =
+            self.find(frame.source_address(), frame.destination_address())
+        else {
+            return Ok(None);
+        };
+        let session = self.sessions[index].as_mut().unwrap();
+
+        if now > session.deadline {
+            session.open = false;
+            return Err(TransportError::Timeout);
+        }
+
+        let packet = DataTransfer::new(frame.data())?;
+        let sequence = packet.sequence();
+
+        if sequence == 0 || sequence > session.packets {
+            session.open = false;
+            return Err(TransportError::BadSequence);
+        }
+
+        let start = (sequence as usize - 1) * PACKET_LEN;
+        let end = (start + PACKET_LEN).min(session.size);
+        session.buffer[start..end]
+            .copy_from_slice(&packet.data()[..end - start]);
+        session.received[sequence as usize / 32] |= 1 << (sequence % 32);
+        session.deadline = now + session.timeout();
+
+        if !session.is_broadcast() || sequence < session.packets {
+            return Ok(None);
+        }
+
+        session.open = false;
+
+        if !session.is_complete() {
+            return Err(TransportError::BadSequence);
+        }
+
+        Ok(Some(session.reassembled()))
+    }
+
+    fn start(
+        &mut self,
+        now: Instant,
+        pgn: Pgn,
+        source_address: Address,
+        destination_address: Address,
+        size: usize,
+        packet_count: u8,
+    ) -> Result<(), TransportError> {
+        // a new session between the same nodes replaces the old one
+        let index = self.find(source_address, destination_address);
+        if let Some(index) = index {
+            self.sessions[index].as_mut().unwrap().open = false;
+        }
+
+        let min = if destination_address.is_global() {
+            1
+        } else {
+            PACKET_LEN + 2
+        };
+
+        if size < min
+            || size > MAX_LEN
+            || packets(size) != packet_count as usize
+        {
+            return Err(TransportError::InvalidMessage);
+        }
+
+        if size > N {
+            return Err(TransportError::TooLarge);
+        }
+
+        let index = index
+            .or_else(|| {
+                self.sessions.iter().position(|session| {
+                    session.as_ref().is_none_or(|session| !session.open)
+                })
+            })
+            .ok_or(TransportError::Busy)?;
+
+        // reuse the buffer of a closed session rather than copying a new one
+        let session = match &mut self.sessions[index] {
+            Some(session) => session,
+            slot => slot.insert(Session {
+                pgn,
+                source_address,
+                destination_address,
+                size,
+                packets: packet_count,
+                received: [0; 8],
+                deadline: now,
+                open: true,
+                buffer: [0; N],
+            }),
+        };
+
+        session.pgn = pgn;
+        session.source_address = source_address;
+        session.destination_address = destination_address;
+        session.size = size;
+        session.packets = packet_count;
+        session.received = [0; 8];
+        session.deadline = now + session.timeout();
+        session.open = true;
+
+        Ok(())
+    }
+
+    fn find(
+        &self,
+        source_address: Address,
+        destination_address: Address,
+    ) -> Option<usize> {
+        self.sessions.iter().position(|session| {
+            session.as_ref().is_some_and(|session| {
+                session.open
+                    && session.source_address == source_address
+                    && session.destination_address == destination_address
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{at, frame};
+
+    fn receive<const S: usize, const N: usize>(
+        sniffer: &mut Sniffer<S, N>,
+        millis: u64,
+        id: u32,
+        data: &[u8],
+    ) -> Result<Option<([u8; 16], usize)>, TransportError> {
+        let frame = frame(id, data);
+        let view = J1939Frame::new(&frame).unwrap();
+
+        sniffer.receive(at(millis), &view).map(|message| {
+            message.map(|message| {
+                let mut data = [0; 16];
+                data[..message.data().len()].copy_from_slice(message.data());
+                (data, message.data().len())
+            })
+        })
+    }
+
+    #[test]
+    fn broadcast() {
+        let mut sniffer = Sniffer::<2, 16>::new();
+
+        let bam = [0x20, 10, 0, 2, 0xFF, 0xCA, 0xFE, 0x00];
+        assert_eq!(receive(&mut sniffer, 0, 0x1CEC_FF00, &bam), Ok(None));
+        assert_eq!(
+            receive(&mut sniffer, 50, 0x1CEB_FF00, &[1, 1, 2, 3, 4, 5, 6, 7]),
+            Ok(None)
+        );
+
+        let (data, len) = receive(
+            &mut sniffer,
+            100,
+            0x1CEB_FF00,
+            &[2, 8, 9, 10, 0xFF, 0xFF, 0xFF, 0xFF],
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(&data[..len], &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        assert!(sniffer.is_empty());
+
+        // missing packet
+        assert_eq!(receive(&mut sniffer, 200, 0x1CEC_FF00, &bam), Ok(None));
+        assert_eq!(
+            receive(&mut sniffer, 250, 0x1CEB_FF00, &[2, 0, 0, 0, 0, 0, 0, 0]),
+            Err(TransportError::BadSequence)
+        );
+    }
+
+    #[test]
+    fn connection_with_retransmit() {
+        let mut sniffer = Sniffer::<2, 16>::new();
+
+        // 0xF9 sends 16 bytes of PGN 0xD800 to 0x00
+        let rts = [0x10, 16, 0, 3, 0xFF, 0x00, 0xD8, 0x00];
+        assert_eq!(receive(&mut sniffer, 0, 0x1CEC_00F9, &rts), Ok(None));
+
+        let cts = [0x11, 3, 1, 0xFF, 0xFF, 0x00, 0xD8, 0x00];
+        assert_eq!(receive(&mut sniffer, 10, 0x1CEC_F900, &cts), Ok(None));
+        for sequence in 1..=3 {
+            let data = [sequence, 0xEE, 0xEE, 0xEE, 0xEE, 0xEE, 0xEE, 0xEE];
+            assert_eq!(receive(&mut sniffer, 20, 0x1CEB_00F9, &data), Ok(None));
+        }
+
+        // receiver asks for the second packet again
+        let cts = [0x11, 1, 2, 0xFF, 0xFF, 0x00, 0xD8, 0x00];
+        assert_eq!(receive(&mut sniffer, 30, 0x1CEC_F900, &cts), Ok(None));
+        let data = [2, 1, 2, 3, 4, 5, 6, 7];
+        assert_eq!(receive(&mut sniffer, 40, 0x1CEB_00F9, &data), Ok(None));
+
+        let ack = [0x13, 16, 0, 3, 0xFF, 0x00, 0xD8, 0x00];
+        let (data, len) = receive(&mut sniffer, 50, 0x1CEC_F900, &ack)
+            .unwrap()
+            .unwrap();
+        assert_eq!(len, 16);
+        assert_eq!(&data[7..14], &[1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(&data[14..], &[0xEE, 0xEE]);
+    }
+
+    #[test]
+    fn aborted() {
+        let mut sniffer = Sniffer::<2, 16>::new();
+
+        let rts = [0x10, 16, 0, 3, 0xFF, 0x00, 0xD8, 0x00];
+        receive(&mut sniffer, 0, 0x1CEC_00F9, &rts).unwrap();
+        assert_eq!(sniffer.len(), 1);
+
+        // abort from the receiver
+        let abort = [0xFF, 3, 0xFF, 0xFF, 0xFF, 0x00, 0xD8, 0x00];
+        assert_eq!(
+            receive(&mut sniffer, 10, 0x1CEC_F900, &abort),
+            Err(TransportError::Aborted)
+        );
+        assert!(sniffer.is_empty());
+    }
+
+    #[test]
+    fn capacity_and_timeout() {
+        let mut sniffer = Sniffer::<1, 16>::new();
+
+        let bam = [0x20, 10, 0, 2, 0xFF, 0xCA, 0xFE, 0x00];
+        receive(&mut sniffer, 0, 0x1CEC_FF00, &bam).unwrap();
+        assert_eq!(
+            receive(&mut sniffer, 0, 0x1CEC_FF01, &bam),
+            Err(TransportError::Busy)
+        );
+
+        assert_eq!(sniffer.poll(at(750)), Ok(()));
+        assert_eq!(sniffer.poll(at(751)), Err(TransportError::Timeout));
+        assert_eq!(receive(&mut sniffer, 800, 0x1CEC_FF01, &bam), Ok(None));
+    }
+}
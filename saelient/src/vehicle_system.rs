@@ -0,0 +1,102 @@
+//! Vehicle system information.
+
+use crate::name::IndustryGroup;
+
+/// Returns the name of a NAME vehicle system within its industry group.
+pub const fn vehicle_system_name(
+    industry_group: IndustryGroup,
+    vehicle_system: u8,
+) -> Option<&'static str> {
+    let key = (industry_group, vehicle_system);
+
+    // generated by scripts/name_tables.py from scripts/vehicle_systems.txt
+    Some(match key {
+        (_, 0) => "Non-specific System",
+        (IndustryGroup::OnHighway, 1) => "Tractor",
+        (IndustryGroup::OnHighway, 2) => "Trailer",
+        (IndustryGroup::AgriculturalAndForestry, 1) => "Tractor",
+        (IndustryGroup::AgriculturalAndForestry, 2) => "Tillage",
+        (IndustryGroup::AgriculturalAndForestry, 3) => "Secondary Tillage",
+        (IndustryGroup::AgriculturalAndForestry, 4) => "Planters/Seeders",
+        (IndustryGroup::AgriculturalAndForestry, 5) => "Fertilizers",
+        (IndustryGroup::AgriculturalAndForestry, 6) => "Sprayers",
+        (IndustryGroup::AgriculturalAndForestry, 7) => "Harvesters",
+        (IndustryGroup::AgriculturalAndForestry, 8) => "Root Harvesters",
+        (IndustryGroup::AgriculturalAndForestry, 9) => "Forage",
+        (IndustryGroup::AgriculturalAndForestry, 10) => "Irrigation",
+        (IndustryGroup::AgriculturalAndForestry, 11) => "Transport/Trailer",
+        (IndustryGroup::AgriculturalAndForestry, 12) => "Farm Yard Operations",
+        (IndustryGroup::AgriculturalAndForestry, 13) => {
+            "Powered Auxiliary Devices"
+        }
+        (IndustryGroup::AgriculturalAndForestry, 14) => "Special Crops",
+        (IndustryGroup::AgriculturalAndForestry, 15) => "Earth Work",
+        (IndustryGroup::AgriculturalAndForestry, 16) => "Skidder",
+        (IndustryGroup::AgriculturalAndForestry, 17) => "Sensor Systems",
+        (IndustryGroup::AgriculturalAndForestry, 19) => "Timber Harvesters",
+        (IndustryGroup::AgriculturalAndForestry, 20) => "Forwarders",
+        (IndustryGroup::AgriculturalAndForestry, 21) => "Timber Loaders",
+        (IndustryGroup::AgriculturalAndForestry, 22) => {
+            "Timber Processing Machines"
+        }
+        (IndustryGroup::AgriculturalAndForestry, 23) => "Mulchers",
+        (IndustryGroup::AgriculturalAndForestry, 24) => "Utility Vehicles",
+        (IndustryGroup::Construction, 1) => "Skid Steer Loader",
+        (IndustryGroup::Construction, 2) => "Articulated Dump Truck",
+        (IndustryGroup::Construction, 3) => "Backhoe",
+        (IndustryGroup::Construction, 4) => "Crawler",
+        (IndustryGroup::Construction, 5) => "Excavator",
+        (IndustryGroup::Construction, 6) => "Forklift",
+        (IndustryGroup::Construction, 7) => "Four Wheel Drive Loader",
+        (IndustryGroup::Construction, 8) => "Grader",
+        (IndustryGroup::Construction, 9) => "Milling Machine",
+        (IndustryGroup::Construction, 10) => "Recycler",
+        (IndustryGroup::Construction, 11) => "Paver",
+        (IndustryGroup::Construction, 12) => "Compactor",
+        (IndustryGroup::Marine, 10) => "System Tools",
+        (IndustryGroup::Marine, 20) => "Safety Systems",
+        (IndustryGroup::Marine, 25) => "Internetwork Device",
+        (IndustryGroup::Marine, 30) => "Electrical Distribution",
+        (IndustryGroup::Marine, 35) => "Electrical Generation",
+        (IndustryGroup::Marine, 40) => "Steering and Control Surfaces",
+        (IndustryGroup::Marine, 50) => "Propulsion",
+        (IndustryGroup::Marine, 60) => "Navigation",
+        (IndustryGroup::Marine, 70) => "Communication",
+        (IndustryGroup::Marine, 75) => "Sensor Communication Interface",
+        (IndustryGroup::Marine, 80) => "Instrumentation/General Systems",
+        (IndustryGroup::Marine, 85) => "External Environment",
+        (IndustryGroup::Marine, 90) => "Internal Environment",
+        (IndustryGroup::Marine, 100) => "Deck, Cargo and Fishing Equipment",
+        (IndustryGroup::Marine, 110) => "Human Interface",
+        (IndustryGroup::Marine, 120) => "Display",
+        (IndustryGroup::Marine, 125) => "Entertainment",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample() {
+        assert_eq!(
+            vehicle_system_name(IndustryGroup::Global, 0),
+            Some("Non-specific System")
+        );
+        assert_eq!(
+            vehicle_system_name(IndustryGroup::OnHighway, 2),
+            Some("Trailer")
+        );
+        assert_eq!(
+            vehicle_system_name(IndustryGroup::Construction, 4),
+            Some("Crawler")
+        );
+        assert_eq!(
+            vehicle_system_name(IndustryGroup::Marine, 60),
+            Some("Navigation")
+        );
+        assert_eq!(vehicle_system_name(IndustryGroup::OnHighway, 60), None);
+        assert_eq!(vehicle_system_name(IndustryGroup::Marine, 127), None);
+    }
+}